@@ -1,12 +1,20 @@
 extern crate wasm_terminal_2048;
 extern crate termion;
 
-use wasm_terminal_2048::game::{Game};
-use wasm_terminal_2048::tile::{Direction};
+use wasm_terminal_2048::agent;
+use wasm_terminal_2048::args::{parse_arg_from, parse_size};
+use wasm_terminal_2048::evaluator::Evaluator;
+use wasm_terminal_2048::game::{Game, GameStatus};
+use wasm_terminal_2048::solver::Solver;
+use wasm_terminal_2048::spawn::{Difficulty, SpawnPolicy};
+use wasm_terminal_2048::tile::{Board, Direction};
 
+use std::env;
+use std::fs;
 use std::io::{stdin};
 use std::io::{stdout, Write};
 use std::io::{Stdout};
+use std::time::Duration;
 use termion::event::Key;
 use termion::input::TermRead;
 use termion::raw::IntoRawMode;
@@ -14,57 +22,250 @@ use termion::raw::IntoRawMode;
 mod gui { 
 pub const CONTROL_TEXT: &str = "    ============= 2048 ============= \n\r
     UP-DOWN-RIGHT-LEFT: direction \n\r
-    k-j-h-l: direction \n\r
+    k-j-h-l / w-s-a-d: direction \n\r
     n: new game \n\r
+    c: keep going after winning \n\r
+    u-r: undo-redo \n\r
+    p: print the board notation \n\r
+    ?: hint the best direction \n\r
     q: quit game \n\r
     ================================ \n\r
     Current steps:  "; 
+pub const SCORE_TEXT: &str = "    Score: ";
+pub const BEST_SCORE_TEXT: &str = "    Best: ";
+pub const SEED_TEXT: &str = "    Seed: ";
+pub const DIFFICULTY_TEXT: &str = "    Difficulty: ";
+pub const UNDOS_LEFT_TEXT: &str = "    Undos left: ";
+pub const NOTATION_TEXT: &str = "    Board: ";
+pub const HINT_TEXT: &str = "    Hint: ";
+pub const NO_HINT_TEXT: &str = "    Hint: no movement is possible \n\r";
+pub const WON_TEXT: &str = "    *** You win! press c to keep going, n for a new game *** \n\r";
+pub const LOST_TEXT: &str = "    *** Game over! press n for a new game *** \n\r";
+pub const RESUME_TEXT: &str = "    Resume the saved game? (y/n) \n\r";
+pub const SAVE_TEXT: &str = "    Save the game before quitting? (y/n) \n\r";
+pub const LOAD_ERROR_TEXT: &str = "    Failed to load the saved game, press any key: ";
+pub const SAVE_ERROR_TEXT: &str = "    Failed to save the game, press any key: ";
 }
 
-fn render(stdout: &mut Stdout, game: &Game) {
-    write!(stdout, "{}{}{}{}{}\n {}\n\r", 
+/// The default thinking time of a hint in milliseconds
+const DEFAULT_HINT_BUDGET: u64 = 200;
+
+/// The maximum depth of the search of a hint, which is usually cut by the thinking time
+const HINT_MAX_DEPTH: u32 = 8;
+
+fn status_text<P: SpawnPolicy>(game: &Game<P>) -> &'static str {
+    match game.get_status() {
+        GameStatus::Won => gui::WON_TEXT,
+        GameStatus::Lost => gui::LOST_TEXT,
+        GameStatus::Playing | GameStatus::WonContinuing => "",
+    }
+}
+
+fn undos_left_text<P: SpawnPolicy>(game: &Game<P>) -> String {
+    match game.get_undos_left() {
+        Some(left) => format!("{}{}\n\r", gui::UNDOS_LEFT_TEXT, left),
+        None => String::new(),
+    }
+}
+
+fn render<P: SpawnPolicy>(stdout: &mut Stdout, game: &Game<P>, difficulty: Difficulty) {
+    write!(stdout, "{}{}{}{}{}{}\n\r{}{}\n\r{}{}\n\r{}{}\n\r{}{}\n\r{}\n {}\n\r", 
         termion::clear::All,
         termion::cursor::Goto(1,1),
         game.get_board(),
+        status_text(game),
         gui::CONTROL_TEXT,
         game.get_steps(),
+        gui::SCORE_TEXT,
+        game.get_score(),
+        gui::BEST_SCORE_TEXT,
+        game.get_best_score(),
+        gui::SEED_TEXT,
+        game.get_seed(),
+        gui::DIFFICULTY_TEXT,
+        difficulty.name(),
+        undos_left_text(game),
         termion::cursor::Hide).unwrap();
     stdout.flush().unwrap();
 }
 
+/// Get the arrow of the direction
+fn arrow(dir: Direction) -> char {
+    match dir {
+        Direction::Left => '←',
+        Direction::Right => '→',
+        Direction::Up => '↑',
+        Direction::Down => '↓',
+    }
+}
+
+/// Search the best direction of the board, and write it with the value of each direction
+fn hint_text<E: Evaluator>(solver: &mut Solver<E>, board: &Board) -> String {
+    let values = solver.evaluate_moves(board);
+    // picked as `Solver::best_move`, so the hint is always the movement of the AI
+    match agent::pick(values.iter().copied()) {
+        Some(dir) => {
+            let texts: Vec<String> = values.iter()
+                .map(|(other, value)| format!("{} {}: {:.0}", arrow(*other), other.name(), value))
+                .collect();
+            format!("{}{} {}\n\r    {}\n\r", gui::HINT_TEXT, arrow(dir), dir.name(), texts.join("  "))
+        },
+        None => gui::NO_HINT_TEXT.to_string(),
+    }
+}
+
+/// Parse the value of the given command line option, e.g. `--seed <seed>`. Exits with the usage
+/// if the value is missing or invalid.
+fn parse_arg<T>(name: &str, usage: &str, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+    let args: Vec<String> = env::args().collect();
+    parse_arg_from(&args, name, usage, parse).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    })
+}
+
+/// Get the default path of the save file, which is in the home directory
+fn default_save_file() -> String {
+    let home = env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    format!("{}/.wasm_terminal_2048_save", home)
+}
+
+/// Write the prompt and wait for a key, returns true if the key is `y`
+fn confirm(stdout: &mut Stdout, keys: &mut impl Iterator<Item = std::io::Result<Key>>, prompt: &str) -> bool {
+    write!(stdout, "{}", prompt).unwrap();
+    stdout.flush().unwrap();
+    matches!(keys.next(), Some(Ok(Key::Char('y'))))
+}
+
+/// Load the game from the save file, which starts with the difficulty followed by the game
+fn load_game(path: &str) -> Result<(Game<Box<dyn SpawnPolicy + Send>>, Difficulty), String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let (first, rest) = text.split_once('\n').unwrap_or((&text, ""));
+    let difficulty = first.strip_prefix("difficulty ")
+        .and_then(Difficulty::from_name)
+        .ok_or_else(|| format!("invalid difficulty `{}`", first))?;
+    let game = Game::load(rest, difficulty.policy()).map_err(|err| err.to_string())?;
+    Ok((game, difficulty))
+}
+
+/// Save the game to the save file with the difficulty
+fn save_game<P: SpawnPolicy>(path: &str, game: &Game<P>, difficulty: Difficulty) -> std::io::Result<()> {
+    fs::write(path, format!("difficulty {}\n{}", difficulty.name(), game.save()))
+}
+
 fn main() {
-    let stdin = stdin();
+    let seed = parse_arg("--seed", "<u64>", |value| value.parse().ok());
+    let mut difficulty = parse_arg("--difficulty", "<easy|normal|hard|expert>", Difficulty::from_name)
+        .unwrap_or_default();
+    let undo_limit = parse_arg("--undo-limit", "<u32>", |value| value.parse().ok());
+    let size = parse_arg("--size", "<rows>x<cols>, e.g. 5x5", parse_size);
+    let save_file = parse_arg("--save-file", "<path>", |value| Some(value.to_string()))
+        .unwrap_or_else(default_save_file);
+    let hint_budget = parse_arg("--hint-budget", "<milliseconds>", |value| value.parse().ok())
+        .unwrap_or(DEFAULT_HINT_BUDGET);
+    let mut solver = Solver::new(HINT_MAX_DEPTH);
+    solver.set_time_budget(Some(Duration::from_millis(hint_budget)));
+    let mut keys = stdin().keys();
     // using raw mode to accept single key input
     let mut stdout = stdout().into_raw_mode().unwrap();
 
-    // start a new game
-    let mut game = Game::new();
-    game.start_game();
-    render(&mut stdout, &game);
+    // start a new game, which can be reproduced by the given seed
+    let mut game = Game::with_policy(difficulty.policy());
+    game.set_undo_limit(undo_limit);
+    if let Some((rows, cols)) = size {
+        game.set_size(rows, cols);
+    }
+    match seed {
+        Some(seed) => game.start_game_with_seed(seed),
+        None => game.start_game(),
+    }
+
+    // resume the saved game unless a new game is asked by the seed
+    if seed.is_none() && fs::metadata(&save_file).is_ok()
+        && confirm(&mut stdout, &mut keys, gui::RESUME_TEXT) {
+        match load_game(&save_file) {
+            Ok((saved, saved_difficulty)) => {
+                game = saved;
+                difficulty = saved_difficulty;
+                fs::remove_file(&save_file).ok();
+            },
+            Err(err) => {
+                confirm(&mut stdout, &mut keys, &format!("{}{}\n\r", gui::LOAD_ERROR_TEXT, err));
+            },
+        }
+    }
+    render(&mut stdout, &game, difficulty);
              
     // key handle loop
-    for c in stdin.keys() {
-        let mut flag_start_new_game = false;
-        match c.unwrap() {
-            Key::Char('q') => break,
-            Key::Char('n') => {game.start_game(); flag_start_new_game = true;},
-            Key::Right | Key::Char('l') =>  game.action(Direction::Right), 
-            Key::Left | Key::Char('h')=>  game.action(Direction::Left), 
-            Key::Up | Key::Char('k') =>  game.action(Direction::Up), 
-            Key::Down |  Key::Char('j')=>  game.action(Direction::Down), 
+    while let Some(c) = keys.next() {
+        let info = match c.unwrap() {
+            Key::Char('q') => {
+                if confirm(&mut stdout, &mut keys, gui::SAVE_TEXT) {
+                    if let Err(err) = save_game(&save_file, &game, difficulty) {
+                        confirm(&mut stdout, &mut keys, &format!("{}{}\n\r", gui::SAVE_ERROR_TEXT, err));
+                    }
+                }
+                break;
+            },
+            Key::Char('n') => {game.start_game(); render(&mut stdout, &game, difficulty); continue;},
+            Key::Char('c') => {game.keep_going(); render(&mut stdout, &game, difficulty); continue;},
+            Key::Char('u') => {if game.undo() {render(&mut stdout, &game, difficulty);} continue;},
+            Key::Char('r') => {if game.redo() {render(&mut stdout, &game, difficulty);} continue;},
+            Key::Char('p') => {
+                render(&mut stdout, &game, difficulty);
+                write!(stdout, "{}{:#}\n\r", gui::NOTATION_TEXT, game.get_board()).unwrap();
+                stdout.flush().unwrap();
+                continue;
+            },
+            Key::Char('?') => {
+                render(&mut stdout, &game, difficulty);
+                write!(stdout, "{}", hint_text(&mut solver, game.get_board())).unwrap();
+                stdout.flush().unwrap();
+                continue;
+            },
+            Key::Right =>  game.action(Direction::Right), 
+            Key::Left =>  game.action(Direction::Left), 
+            Key::Up =>  game.action(Direction::Up), 
+            Key::Down =>  game.action(Direction::Down), 
+            // vim and WASD keys
+            Key::Char(c) => match c.to_string().parse() {
+                Ok(dir) => game.action(dir),
+                Err(_) => continue,
+            },
             _ => continue,
-        }
-
-        if flag_start_new_game { 
-            render(&mut stdout, &game);
-            continue;
-        }
+        };
 
-        let r = game.next();
-        if r {
-            render(&mut stdout, &game);
+        // a new tile is generated by the game only if any tile is moved
+        if info.moved {
+            render(&mut stdout, &game, difficulty);
         } 
     }
 }
 
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasm_terminal_2048::evaluator::EmptyTiles;
+
+    #[test]
+    fn test_hint_text() {
+        // right and down leave the same empty tiles
+        let board: Board = "2,0,0/0,0,0/0,0,0".parse().unwrap();
+        let mut solver = Solver::with_evaluator(EmptyTiles, 0);
+        assert_eq!(solver.best_move(&board), Some(Direction::Right));
+        let text = hint_text(&mut solver, &board);
+        assert!(text.starts_with(&format!("{}→ Right", gui::HINT_TEXT)), "{}", text);
+
+        for board in ["2,0,0,2/0,4,0,0/0,0,8,0/2,0,0,0", "2,4,8,16/0,0,0,0/4,0,0,2/0,0,0,0"].iter() {
+            let board: Board = board.parse().unwrap();
+            let mut solver = Solver::new(2);
+            let dir = solver.best_move(&board).unwrap();
+            let text = hint_text(&mut solver, &board);
+            assert!(text.starts_with(&format!("{}{} {}", gui::HINT_TEXT, arrow(dir), dir.name())), "{}", text);
+        }
+        assert_eq!(hint_text(&mut Solver::new(2), &"2,4/4,2".parse().unwrap()), gui::NO_HINT_TEXT);
+    }
+}
@@ -0,0 +1,388 @@
+extern crate wasm_terminal_2048;
+
+use wasm_terminal_2048::agent::{self, Agent, BUILTIN_AGENTS};
+use wasm_terminal_2048::args::{parse_arg_from, parse_size};
+use wasm_terminal_2048::game::{Game, GameStatus};
+use wasm_terminal_2048::ntuple::NTupleNetwork;
+use wasm_terminal_2048::spawn::{Difficulty, SpawnPolicy};
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// The spawn policy of the simulated games
+type Policy = Box<dyn SpawnPolicy + Send>;
+
+/// The tiles whose reaching rates are reported
+const REACHED_TILES: [u32; 3] = [2048, 4096, 8192];
+
+/// The percentiles of scores that are reported
+const PERCENTILES: [u32; 5] = [10, 25, 50, 75, 90];
+
+/// The agent that plays the games
+///
+/// * `Builtin` A built-in agent of the library by the name, see `agent::builtin`
+/// * `NTuple` A trained n-tuple network, which is shared by all games
+enum AgentSpec {
+    Builtin(String),
+    NTuple(NTupleNetwork),
+}
+
+impl AgentSpec {
+    /// Get the name of the agent
+    fn name(&self) -> &str {
+        match self {
+            AgentSpec::Builtin(name) => name,
+            AgentSpec::NTuple(_) => "ntuple",
+        }
+    }
+
+    /// Create the agent of a game, which is seeded by the seed of the game, so the result of
+    /// the game does not depend on the thread that plays it
+    fn create(&self, seed: u64) -> Box<dyn Agent<Policy> + Send + '_> {
+        match self {
+            AgentSpec::Builtin(name) => agent::builtin(name, seed).unwrap(),
+            AgentSpec::NTuple(network) => Box::new(network),
+        }
+    }
+}
+
+/// The format of the report
+///
+/// * `Text` The summary for humans
+/// * `Csv` A line of the seed, score, max tile and moves for each game
+/// * `Json` The summary and the result of each game
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Format::Text),
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// The result of a game
+struct GameResult {
+    seed: u64,
+    score: u32,
+    max_tile: u32,
+    moves: u32,
+}
+
+/// The settings of the simulation
+struct Simulation {
+    agent: AgentSpec,
+    difficulty: Difficulty,
+    size: (usize, usize),
+    first_seed: u64,
+    games: u64,
+    threads: usize,
+}
+
+impl Simulation {
+    /// Play a game by the agent until it is lost. A won game keeps going.
+    fn play(&self, seed: u64) -> GameResult {
+        let mut game = Game::with_policy(self.difficulty.policy());
+        game.set_history_size(0);
+        game.set_size(self.size.0, self.size.1);
+        game.start_game_with_seed(seed);
+        let mut agent = self.agent.create(seed);
+        while let Some(dir) = agent.choose(&game) {
+            game.action(dir);
+            if game.get_status() == GameStatus::Won {
+                game.keep_going();
+            }
+        }
+        GameResult {
+            seed,
+            score: game.get_score(),
+            max_tile: game.get_board().max_tile(),
+            moves: game.get_steps(),
+        }
+    }
+
+    /// Play all the games in the threads, which take the next game in turn. Returns the
+    /// results ordered by the seeds.
+    fn run(&self) -> Vec<GameResult> {
+        let next = AtomicU64::new(0);
+        let mut results: Vec<GameResult> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.threads)
+                .map(|_| scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        if idx >= self.games {
+                            break results;
+                        }
+                        results.push(self.play(self.first_seed.wrapping_add(idx)));
+                    }
+                }))
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        });
+        results.sort_by_key(|result| result.seed.wrapping_sub(self.first_seed));
+        results
+    }
+}
+
+/// The statistics of the results
+struct Summary {
+    // the scores in ascending order
+    scores: Vec<u32>,
+    mean_score: f64,
+    mean_moves: f64,
+    max_tiles: BTreeMap<u32, usize>,
+    elapsed: Duration,
+}
+
+impl Summary {
+    fn new(results: &[GameResult], elapsed: Duration) -> Self {
+        let games = results.len().max(1) as f64;
+        let mut scores: Vec<u32> = results.iter().map(|result| result.score).collect();
+        scores.sort_unstable();
+        let mut max_tiles = BTreeMap::new();
+        for result in results.iter() {
+            *max_tiles.entry(result.max_tile).or_insert(0) += 1;
+        }
+        Summary {
+            mean_score: scores.iter().map(|score| f64::from(*score)).sum::<f64>() / games,
+            mean_moves: results.iter().map(|result| f64::from(result.moves)).sum::<f64>() / games,
+            scores,
+            max_tiles,
+            elapsed,
+        }
+    }
+
+    /// Get the score of the given percentile by the nearest rank, which is the smallest score
+    /// that is not less than the percentile of the scores. The 0th percentile is the minimum.
+    fn percentile(&self, percentile: u32) -> u32 {
+        if self.scores.is_empty() {
+            return 0;
+        }
+        let rank = (self.scores.len() * percentile as usize).div_ceil(100);
+        self.scores[rank.clamp(1, self.scores.len()) - 1]
+    }
+
+    /// Get the rate of the games that reach the given tile
+    fn reached(&self, tile: u32) -> f64 {
+        let count: usize = self.max_tiles.range(tile..).map(|(_, count)| count).sum();
+        count as f64 / self.scores.len().max(1) as f64
+    }
+
+    fn games_per_second(&self) -> f64 {
+        self.scores.len() as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Write the summary for humans
+fn text_report(sim: &Simulation, summary: &Summary) -> String {
+    let mut text = format!("agent: {}, difficulty: {}, size: {}x{}, seeds: {}..{}\n",
+        sim.agent.name(), sim.difficulty.name(), sim.size.0, sim.size.1,
+        sim.first_seed, sim.first_seed.wrapping_add(sim.games));
+    text.push_str(&format!("games: {}, threads: {}, elapsed: {:.2}s, games/s: {:.2}\n",
+        summary.scores.len(), sim.threads, summary.elapsed.as_secs_f64(), summary.games_per_second()));
+    let percentiles: Vec<String> = PERCENTILES.iter()
+        .map(|percentile| format!("p{} {}", percentile, summary.percentile(*percentile)))
+        .collect();
+    text.push_str(&format!("score: min {}, {}, max {}, mean {:.1}\n",
+        summary.percentile(0), percentiles.join(", "), summary.percentile(100), summary.mean_score));
+    text.push_str(&format!("moves: mean {:.1}\n", summary.mean_moves));
+    text.push_str("max tile:\n");
+    for (tile, count) in summary.max_tiles.iter() {
+        text.push_str(&format!("  {:>6}: {} ({:.1}%)\n", tile, count, *count as f64 * 100.0 / summary.scores.len() as f64));
+    }
+    let reached: Vec<String> = REACHED_TILES.iter()
+        .map(|tile| format!("{} {:.1}%", tile, summary.reached(*tile) * 100.0))
+        .collect();
+    text.push_str(&format!("reached: {}\n", reached.join(", ")));
+    text
+}
+
+/// Write a line of the seed, score, max tile and moves for each game
+fn csv_report(results: &[GameResult]) -> String {
+    let mut text = String::from("seed,score,max_tile,moves\n");
+    for result in results.iter() {
+        text.push_str(&format!("{},{},{},{}\n", result.seed, result.score, result.max_tile, result.moves));
+    }
+    text
+}
+
+/// Write the summary and the result of each game
+fn json_report(sim: &Simulation, summary: &Summary, results: &[GameResult]) -> String {
+    let percentiles: Vec<String> = PERCENTILES.iter()
+        .map(|percentile| format!("\"p{}\":{}", percentile, summary.percentile(*percentile)))
+        .collect();
+    let max_tiles: Vec<String> = summary.max_tiles.iter()
+        .map(|(tile, count)| format!("\"{}\":{}", tile, count))
+        .collect();
+    let reached: Vec<String> = REACHED_TILES.iter()
+        .map(|tile| format!("\"{}\":{}", tile, summary.reached(*tile)))
+        .collect();
+    let games: Vec<String> = results.iter()
+        .map(|result| format!("{{\"seed\":{},\"score\":{},\"max_tile\":{},\"moves\":{}}}",
+            result.seed, result.score, result.max_tile, result.moves))
+        .collect();
+    format!("{{\"agent\":\"{}\",\"difficulty\":\"{}\",\"size\":\"{}x{}\",\"first_seed\":{},\"games\":{},\
+        \"threads\":{},\"elapsed_secs\":{},\"games_per_sec\":{},\
+        \"score\":{{\"min\":{},{},\"max\":{},\"mean\":{}}},\"mean_moves\":{},\
+        \"max_tiles\":{{{}}},\"reached\":{{{}}},\"results\":[{}]}}\n",
+        sim.agent.name(), sim.difficulty.name(), sim.size.0, sim.size.1, sim.first_seed, summary.scores.len(),
+        sim.threads, summary.elapsed.as_secs_f64(), summary.games_per_second(),
+        summary.percentile(0), percentiles.join(","), summary.percentile(100), summary.mean_score, summary.mean_moves,
+        max_tiles.join(","), reached.join(","), games.join(","))
+}
+
+/// Exit with the error
+fn exit_with(error: String) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
+}
+
+/// Parse the value of the given command line option, e.g. `--seed <seed>`. Exits with the usage
+/// if the value is missing or invalid.
+fn parse_arg<T>(name: &str, usage: &str, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+    let args: Vec<String> = env::args().collect();
+    parse_arg_from(&args, name, usage, parse).unwrap_or_else(|err| exit_with(err))
+}
+
+fn main() {
+    let agent_usage = format!("<{}|ntuple>", BUILTIN_AGENTS.join("|"));
+    let name = parse_arg("--agent", &agent_usage, |value| {
+            Some(value.to_string()).filter(|name| name == "ntuple" || BUILTIN_AGENTS.contains(&name.as_str()))
+        })
+        .unwrap_or_else(|| "greedy".to_string());
+    let weights = parse_arg("--weights", "<path>", |value| Some(value.to_string()));
+    let games = parse_arg("--games", "<u64>, at least 1", |value| value.parse().ok().filter(|games| *games > 0))
+        .unwrap_or(100);
+    let first_seed = parse_arg("--seed", "<u64>, the seed of the first game", |value| value.parse().ok())
+        .unwrap_or(0);
+    let difficulty = parse_arg("--difficulty", "<easy|normal|hard|expert>", Difficulty::from_name)
+        .unwrap_or_default();
+    let size = parse_arg("--size", "<rows>x<cols>, e.g. 5x5", parse_size).unwrap_or((4, 4));
+    let threads = parse_arg("--threads", "<usize>", |value| value.parse().ok().filter(|threads| *threads > 0))
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+    let format = parse_arg("--format", "<text|csv|json>", Format::from_name).unwrap_or(Format::Text);
+    let output = parse_arg("--output", "<path>", |value| Some(value.to_string()));
+
+    let agent = match (name.as_str(), weights) {
+        ("ntuple", Some(path)) => {
+            let network = NTupleNetwork::load(&path)
+                .unwrap_or_else(|err| exit_with(format!("Failed to load the weights `{}`: {}", path, err)));
+            if (network.get_size(), network.get_size()) != size {
+                exit_with(format!("The weights `{0}` are for {1}x{1} boards, but the size is {2}x{3}",
+                    path, network.get_size(), size.0, size.1));
+            }
+            AgentSpec::NTuple(network)
+        },
+        ("ntuple", None) => exit_with("The ntuple agent needs the weights, usage: --weights <path>".to_string()),
+        _ => AgentSpec::Builtin(name),
+    };
+    let sim = Simulation { agent, difficulty, size, first_seed, games, threads };
+
+    let start = Instant::now();
+    let results = sim.run();
+    let summary = Summary::new(&results, start.elapsed());
+    let report = match format {
+        Format::Text => text_report(&sim, &summary),
+        Format::Csv => csv_report(&results),
+        Format::Json => json_report(&sim, &summary, &results),
+    };
+    match output {
+        Some(path) => fs::write(&path, report)
+            .unwrap_or_else(|err| exit_with(format!("Failed to write the report `{}`: {}", path, err))),
+        None => print!("{}", report),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(seed: u64, score: u32, max_tile: u32) -> GameResult {
+        GameResult { seed, score, max_tile, moves: score / 10 }
+    }
+
+    fn sample_simulation() -> Simulation {
+        Simulation {
+            agent: AgentSpec::Builtin("greedy".to_string()),
+            difficulty: Difficulty::default(),
+            size: (3, 3),
+            first_seed: 5,
+            games: 4,
+            threads: 2,
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        // the scores 10, 20, .., 100 in a shuffled order
+        let results: Vec<GameResult> = [30, 100, 10, 60, 20, 90, 50, 40, 80, 70].iter().enumerate()
+            .map(|(idx, score)| result(idx as u64, *score, if *score >= 80 { 4096 } else { 1024 }))
+            .collect();
+        let summary = Summary::new(&results, Duration::from_secs(2));
+        let percentiles: Vec<u32> = [0, 10, 25, 50, 75, 90, 100].iter()
+            .map(|percentile| summary.percentile(*percentile))
+            .collect();
+        assert_eq!(percentiles, vec![10, 10, 30, 50, 80, 90, 100]);
+        assert_eq!(summary.mean_score, 55.0);
+        assert_eq!(summary.mean_moves, 5.5);
+        assert_eq!(summary.reached(1024), 1.0);
+        assert_eq!(summary.reached(2048), 0.3);
+        assert_eq!(summary.reached(4096), 0.3);
+        assert_eq!(summary.reached(8192), 0.0);
+        assert_eq!(summary.games_per_second(), 5.0);
+
+        let summary = Summary::new(&[result(0, 7, 8)], Duration::from_secs(1));
+        assert!(PERCENTILES.iter().all(|percentile| summary.percentile(*percentile) == 7));
+    }
+
+    #[test]
+    fn test_csv_report() {
+        let results = vec![result(5, 120, 16), result(6, 80, 8)];
+        assert_eq!(csv_report(&results), "seed,score,max_tile,moves\n5,120,16,12\n6,80,8,8\n");
+    }
+
+    #[test]
+    fn test_json_report() {
+        let results = vec![result(5, 120, 16), result(6, 80, 8)];
+        let summary = Summary::new(&results, Duration::from_secs(4));
+        let json: serde_json::Value = serde_json::from_str(&json_report(&sample_simulation(), &summary, &results))
+            .unwrap();
+        assert_eq!(json["agent"], "greedy");
+        assert_eq!(json["size"], "3x3");
+        assert_eq!(json["games"], 2);
+        assert_eq!(json["games_per_sec"], 0.5);
+        assert_eq!(json["score"]["min"], 80);
+        assert_eq!(json["score"]["p50"], 80);
+        assert_eq!(json["score"]["max"], 120);
+        assert_eq!(json["score"]["mean"], 100.0);
+        assert_eq!(json["max_tiles"]["16"], 1);
+        assert_eq!(json["reached"]["2048"], 0.0);
+        assert_eq!(json["results"][0], serde_json::json!({"seed": 5, "score": 120, "max_tile": 16, "moves": 12}));
+        assert_eq!(json["results"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_run() {
+        let sim = sample_simulation();
+        let results = sim.run();
+        let seeds: Vec<u64> = results.iter().map(|result| result.seed).collect();
+        assert_eq!(seeds, vec![5, 6, 7, 8]);
+        // the results do not depend on the threads
+        let single = Simulation { threads: 1, ..sample_simulation() }.run();
+        assert!(results.iter().zip(single.iter())
+            .all(|(a, b)| (a.score, a.max_tile, a.moves) == (b.score, b.max_tile, b.moves)));
+    }
+}
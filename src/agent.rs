@@ -0,0 +1,319 @@
+use crate::evaluator::{Evaluator, LineHeuristic};
+use crate::game::Game;
+use crate::rollout::RolloutAgent;
+use crate::solver::Solver;
+use crate::spawn::{ClassicSpawn, SpawnPolicy};
+use crate::tile::{Board, Direction};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// `Agent` chooses the movement of a game, so the AIs can be plugged in any front-end or tool
+/// in place of the keyboard input. The agent is parameterized over the spawn policy of the
+/// game, which is `ClassicSpawn` by default.
+///
+/// ```ignore
+///  let mut agent = agent::builtin("greedy", 0).unwrap();
+///  while let Some(dir) = agent.choose(&game) {
+///    game.action(dir);
+///  }
+/// ```
+pub trait Agent<P: SpawnPolicy = ClassicSpawn> {
+    /// Choose the movement of the given game. Returns `None` if no movement is possible.
+    ///
+    /// # Arguments
+    /// * `game` The game to play
+    ///
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction>;
+}
+
+impl<P: SpawnPolicy, A: Agent<P> + ?Sized> Agent<P> for Box<A> {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        (**self).choose(game)
+    }
+}
+
+impl<P: SpawnPolicy, A: Agent<P> + ?Sized> Agent<P> for &mut A {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        (**self).choose(game)
+    }
+}
+
+/// Pick the direction of the largest value, the first one wins ties. It is how the AIs choose
+/// a movement by the values of the movements, e.g. `Solver::evaluate_moves`.
+///
+/// # Arguments
+/// * `values` The directions with the values
+///
+pub fn pick(values: impl Iterator<Item = (Direction, f64)>) -> Option<Direction> {
+    values
+        .fold(None, |best: Option<(Direction, f64)>, (dir, value)| match best {
+            Some((_, best_value)) if best_value >= value => best,
+            _ => Some((dir, value)),
+        })
+        .map(|(dir, _)| dir)
+}
+
+/// Get all the possible movements of the board with the moved boards and the scores
+fn moves(board: &Board) -> impl Iterator<Item = (Direction, Board, u32)> + '_ {
+    Direction::ALL.iter()
+        .filter_map(move |dir| board.apply(*dir).map(|(next, info)| (*dir, next, info.score)))
+}
+
+/// `RandomAgent` chooses a possible movement uniformly. The random generator is seeded, so the
+/// same seed always chooses the same movements.
+pub struct RandomAgent {
+    rng: ChaCha8Rng,
+}
+
+impl Default for RandomAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandomAgent {
+    /// Create a new `RandomAgent` whose random generator is seeded by the OS
+    pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    /// Create a new `RandomAgent` whose random generator is seeded by the given seed
+    ///
+    /// # Arguments
+    /// * `seed` The seed of the random generator
+    ///
+    pub fn with_seed(seed: u64) -> Self {
+        RandomAgent { rng: ChaCha8Rng::seed_from_u64(seed) }
+    }
+}
+
+impl<P: SpawnPolicy> Agent<P> for RandomAgent {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        let moves = game.get_board().legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+        Some(moves[self.rng.gen_range(0..moves.len())])
+    }
+}
+
+/// `GreedyAgent` chooses the movement of the largest score without looking ahead, and then
+/// the one that leaves the most empty tiles. The first direction of `Direction::ALL` wins ties.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedyAgent;
+
+impl<P: SpawnPolicy> Agent<P> for GreedyAgent {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        moves(game.get_board())
+            .fold(None, |best: Option<(Direction, (u32, usize))>, (dir, next, score)| {
+                let key = (score, next.empty_tiles().len());
+                match best {
+                    Some((_, best_key)) if best_key >= key => best,
+                    _ => Some((dir, key)),
+                }
+            })
+            .map(|(dir, _)| dir)
+    }
+}
+
+/// `CornerAgent` keeps the largest tiles in the bottom-left corner by cycling through
+/// `Down`, `Left`, `Down` and `Right`, skipping impossible movements. `Up` is only chosen if
+/// no other movement is possible.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CornerAgent {
+    step: usize,
+}
+
+impl CornerAgent {
+    /// The cycle of the movements
+    const CYCLE: [Direction; 4] = [Direction::Down, Direction::Left, Direction::Down, Direction::Right];
+
+    /// Create a new `CornerAgent` that starts at the beginning of the cycle
+    pub fn new() -> Self {
+        CornerAgent { step: 0 }
+    }
+}
+
+impl<P: SpawnPolicy> Agent<P> for CornerAgent {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        let moves = game.get_board().legal_moves();
+        let cycle = Self::CYCLE.len();
+        for offset in 0..cycle {
+            let step = (self.step + offset) % cycle;
+            if moves.contains(&Self::CYCLE[step]) {
+                self.step = (step + 1) % cycle;
+                return Some(Self::CYCLE[step]);
+            }
+        }
+        moves.first().copied()
+    }
+}
+
+/// `HeuristicAgent` chooses the movement whose moved board is valued the most by the
+/// `Evaluator` without looking ahead, which is `LineHeuristic` by default. It is the depth 0
+/// version of `Solver`.
+#[derive(Debug, Clone, Default)]
+pub struct HeuristicAgent<E: Evaluator = LineHeuristic> {
+    evaluator: E,
+}
+
+impl HeuristicAgent {
+    /// Create a new `HeuristicAgent` with `LineHeuristic`
+    pub fn new() -> Self {
+        Self::with_evaluator(LineHeuristic)
+    }
+}
+
+impl<E: Evaluator> HeuristicAgent<E> {
+    /// Create a new `HeuristicAgent` that evaluates boards by the given evaluator
+    ///
+    /// # Arguments
+    /// * `evaluator` The evaluator of boards
+    ///
+    pub fn with_evaluator(evaluator: E) -> Self {
+        HeuristicAgent { evaluator }
+    }
+
+    /// Get the evaluator
+    pub fn get_evaluator(&self) -> &E {
+        &self.evaluator
+    }
+}
+
+impl<P: SpawnPolicy, E: Evaluator> Agent<P> for HeuristicAgent<E> {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        pick(moves(game.get_board()).map(|(dir, next, _)| (dir, self.evaluator.evaluate(&next))))
+    }
+}
+
+impl<P: SpawnPolicy, E: Evaluator> Agent<P> for Solver<E> {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        self.best_move(game.get_board())
+    }
+}
+
+impl<P: SpawnPolicy> Agent<P> for RolloutAgent {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        self.best_move(game.get_board())
+    }
+}
+
+/// The names of the built-in agents, see `builtin`
+pub const BUILTIN_AGENTS: [&str; 6] = ["random", "greedy", "corner", "heuristic", "expectimax", "rollout"];
+
+/// Get the built-in agent by the name with the default settings
+///
+/// * `random` see `RandomAgent`
+/// * `greedy` see `GreedyAgent`
+/// * `corner` see `CornerAgent`
+/// * `heuristic` see `HeuristicAgent`
+/// * `expectimax` see `Solver`
+/// * `rollout` see `RolloutAgent`
+///
+/// # Arguments
+/// * `name` The name of the agent
+/// * `seed` The seed of the random generator, which is ignored by deterministic agents
+///
+pub fn builtin<P: SpawnPolicy>(name: &str, seed: u64) -> Option<Box<dyn Agent<P> + Send>> {
+    match name {
+        "random" => Some(Box::new(RandomAgent::with_seed(seed))),
+        "greedy" => Some(Box::new(GreedyAgent)),
+        "corner" => Some(Box::new(CornerAgent::new())),
+        "heuristic" => Some(Box::new(HeuristicAgent::new())),
+        "expectimax" => Some(Box::new(Solver::default())),
+        "rollout" => {
+            let mut agent = RolloutAgent::default();
+            agent.reseed(seed);
+            Some(Box::new(agent))
+        },
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameStatus;
+
+    fn game_with_board(board: &str) -> Game {
+        Game::new_with_values(board.parse::<Board>().unwrap().get_values())
+    }
+
+    #[test]
+    fn test_pick() {
+        let values = [(Direction::Up, 1.0), (Direction::Left, 3.0), (Direction::Down, 3.0), (Direction::Right, 2.0)];
+        assert_eq!(pick(values.iter().copied()), Some(Direction::Left));
+        assert_eq!(pick(values[2..].iter().copied()), Some(Direction::Down));
+        assert_eq!(pick(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn test_random_agent() {
+        let game = game_with_board("2,4,0/4,2,0/2,4,8");
+        let mut agent1 = RandomAgent::with_seed(3);
+        let mut agent2 = RandomAgent::with_seed(3);
+        for _ in 0..20 {
+            let dir = agent1.choose(&game).unwrap();
+            assert_eq!(agent2.choose(&game), Some(dir));
+            assert!(game.get_board().legal_moves().contains(&dir));
+        }
+        assert_eq!(agent1.choose(&game_with_board("2,4/4,2")), None);
+    }
+
+    #[test]
+    fn test_greedy_agent() {
+        let game = game_with_board("2,2,0/0,8,8/0,0,4");
+        let dir = GreedyAgent.choose(&game).unwrap();
+        assert!(dir == Direction::Left || dir == Direction::Right);
+        assert_eq!(GreedyAgent.choose(&game_with_board("2,4/4,2")), None);
+    }
+
+    #[test]
+    fn test_corner_agent() {
+        let mut agent = CornerAgent::new();
+        let game = game_with_board("0,2,0/0,0,0/4,0,0");
+        let dirs: Vec<Direction> = (0..4).map(|_| agent.choose(&game).unwrap()).collect();
+        assert_eq!(dirs, CornerAgent::CYCLE);
+        // down and left are impossible
+        let game = game_with_board("0,0,0/2,0,0/4,0,0");
+        assert_eq!(agent.choose(&game), Some(Direction::Right));
+        assert_eq!(agent.choose(&game), Some(Direction::Right));
+        // only up is possible
+        let game = game_with_board("0,0/2,4");
+        assert_eq!(agent.choose(&game), Some(Direction::Up));
+    }
+
+    #[test]
+    fn test_heuristic_agent() {
+        use crate::evaluator::EmptyTiles;
+
+        let game = game_with_board("2,2,4,0/0,0,0,0/0,0,0,0/0,0,0,0");
+        let mut agent = HeuristicAgent::with_evaluator(EmptyTiles);
+        let dir = agent.choose(&game).unwrap();
+        assert!(dir == Direction::Left || dir == Direction::Right);
+        assert!(HeuristicAgent::new().choose(&game).is_some());
+    }
+
+    #[test]
+    fn test_builtin() {
+        assert!(builtin::<ClassicSpawn>("unknown", 0).is_none());
+        // every agent plays the same game until it is lost
+        for name in BUILTIN_AGENTS[..4].iter() {
+            let mut agent = builtin(name, 1).unwrap();
+            let mut game = Game::with_seed(1);
+            game.start_game();
+            while let Some(dir) = agent.choose(&game) {
+                game.action(dir);
+                if game.get_status() == GameStatus::Won {
+                    game.keep_going();
+                }
+            }
+            assert_eq!(game.get_status(), GameStatus::Lost, "{}", name);
+        }
+        for name in ["expectimax", "rollout"].iter() {
+            let game = game_with_board("2,0,0,2/0,4,0,0/0,0,8,0/2,0,0,0");
+            assert!(builtin(name, 1).unwrap().choose(&game).is_some());
+        }
+    }
+}
@@ -0,0 +1,62 @@
+/// The range of the number of rows and columns that the front-ends accept
+pub const SIZE_RANGE: std::ops::RangeInclusive<usize> = 2..=8;
+
+/// Parse the value of the given option in the command line arguments, e.g. `--seed <seed>`.
+/// Returns `Ok(None)` if the option is not given, and the error with the usage if the value is
+/// missing or invalid.
+///
+/// # Arguments
+/// * `args` The command line arguments
+/// * `name` The name of the option, which starts with `--`
+/// * `usage` The usage of the value
+/// * `parse` Parse the value, returns `None` if it is invalid
+///
+pub fn parse_arg_from<T>(args: &[String], name: &str, usage: &str, parse: impl Fn(&str) -> Option<T>)
+    -> Result<Option<T>, String> {
+    let idx = match args.iter().position(|arg| arg == name) {
+        Some(idx) => idx,
+        None => return Ok(None),
+    };
+    match args.get(idx + 1).and_then(|value| parse(value)) {
+        Some(value) => Ok(Some(value)),
+        None => Err(format!("Invalid {}, usage: {} {}", &name[2..], name, usage)),
+    }
+}
+
+/// Parse the board size like `5x5`, and each side is in `SIZE_RANGE`
+pub fn parse_size(value: &str) -> Option<(usize, usize)> {
+    let (rows, cols) = value.split_once('x')?;
+    let (rows, cols) = (rows.parse().ok()?, cols.parse().ok()?);
+    if SIZE_RANGE.contains(&rows) && SIZE_RANGE.contains(&cols) {
+        Some((rows, cols))
+    } else {
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arg() {
+        let args: Vec<String> = ["2048", "--seed", "42", "--size"].iter().map(|arg| arg.to_string()).collect();
+        let parse_seed = |value: &str| value.parse::<u64>().ok();
+        assert_eq!(parse_arg_from(&args, "--seed", "<u64>", parse_seed), Ok(Some(42)));
+        assert_eq!(parse_arg_from(&args, "--games", "<u64>", parse_seed), Ok(None));
+        assert_eq!(parse_arg_from(&args, "--size", "<rows>x<cols>", parse_size),
+            Err("Invalid size, usage: --size <rows>x<cols>".to_string()));
+        assert!(parse_arg_from(&args, "--seed", "<u64>", |value| parse_seed(value).filter(|seed| *seed < 10)).is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4x4"), Some((4, 4)));
+        assert_eq!(parse_size("2x8"), Some((2, 8)));
+        assert_eq!(parse_size("1x4"), None);
+        assert_eq!(parse_size("4x9"), None);
+        assert_eq!(parse_size("4"), None);
+        assert_eq!(parse_size("ax4"), None);
+    }
+}
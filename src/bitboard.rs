@@ -0,0 +1,411 @@
+use crate::tile::{exponent_to_value, Board, Direction, MoveInfo};
+use std::sync::OnceLock;
+
+/// The largest exponent that fits in 4 bits, which is the tile 32768
+pub const MAX_BIT_EXPONENT: u8 = 15;
+
+/// `BitBoard` is a 4x4 board packed in 64 bits, 4 bits per tile exponent, for the AI search and
+/// mass simulation. Tile `i` (row by row) is stored in the bits `4 * i .. 4 * i + 4`, so each
+/// row is 16 bits and the movement of a row is looked up in precomputed tables with 65536
+/// entries. Vertical movements transpose the board, move the rows and transpose it back.
+///
+/// The methods follow the `Board` API, and the results are the same as `Board` except that
+/// two 32768 tiles are not merged since 65536 does not fit in 4 bits. `Board` moves 4x4 boards
+/// by `BitBoard` only when the board has no 32768 tile, so its results are always exact.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitBoard(u64);
+
+/// Lookup tables of all 65536 rows
+struct RowTables {
+    left: Vec<u16>,
+    right: Vec<u16>,
+    score: Vec<u32>,
+    merges: Vec<u8>,
+}
+
+/// Get the lookup tables, which are built at the first use
+fn tables() -> &'static RowTables {
+    static TABLES: OnceLock<RowTables> = OnceLock::new();
+    TABLES.get_or_init(build_tables)
+}
+
+/// Unpack a row of 16 bits to 4 exponents
+fn unpack_row(row: u16) -> [u8; 4] {
+    [
+        (row & 0xf) as u8,
+        ((row >> 4) & 0xf) as u8,
+        ((row >> 8) & 0xf) as u8,
+        ((row >> 12) & 0xf) as u8,
+    ]
+}
+
+/// Pack 4 exponents to a row of 16 bits
+fn pack_row(tiles: [u8; 4]) -> u16 {
+    tiles.iter().rev().fold(0, |row, tile| (row << 4) | u16::from(*tile))
+}
+
+/// Slide and merge a row toward the first tile. Two tiles of `MAX_BIT_EXPONENT` are not merged.
+fn slide_row(tiles: [u8; 4]) -> ([u8; 4], u32, u8) {
+    let mut result = [0; 4];
+    let mut len = 0;
+    let mut score = 0;
+    let mut merges = 0;
+    let mut mergeable = false;
+    for tile in tiles.iter().copied().filter(|tile| *tile != 0) {
+        if mergeable && result[len - 1] == tile && tile < MAX_BIT_EXPONENT {
+            result[len - 1] += 1;
+            score += exponent_to_value(result[len - 1]);
+            merges += 1;
+            mergeable = false;
+        } else {
+            result[len] = tile;
+            len += 1;
+            mergeable = true;
+        }
+    }
+    (result, score, merges)
+}
+
+fn build_tables() -> RowTables {
+    let mut tables = RowTables {
+        left: vec![0; 1 << 16],
+        right: vec![0; 1 << 16],
+        score: vec![0; 1 << 16],
+        merges: vec![0; 1 << 16],
+    };
+    for row in 0..=u16::MAX {
+        let tiles = unpack_row(row);
+        let (left, score, merges) = slide_row(tiles);
+        let mut reversed = tiles;
+        reversed.reverse();
+        let (mut right, _, _) = slide_row(reversed);
+        right.reverse();
+
+        let idx = row as usize;
+        tables.left[idx] = pack_row(left);
+        tables.right[idx] = pack_row(right);
+        // merges are the same toward both sides, so is the score
+        tables.score[idx] = score;
+        tables.merges[idx] = merges;
+    }
+    tables
+}
+
+impl BitBoard {
+    /// Create a new `BitBoard` from the packed 64 bits
+    pub fn from_raw(raw: u64) -> Self {
+        BitBoard(raw)
+    }
+
+    /// Get the packed 64 bits
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+
+    /// Create a new `BitBoard` from a `Board`. Returns `None` if the board is not 4x4, or any
+    /// tile is larger than 32768.
+    ///
+    /// # Arguments
+    /// * `board` The board to pack
+    ///
+    pub fn from_board(board: &Board) -> Option<Self> {
+        if board.get_rows() != 4 || board.get_cols() != 4 {
+            return None;
+        }
+        let mut raw = 0;
+        for (idx, exponent) in board.get_exponents().iter().enumerate() {
+            if *exponent > MAX_BIT_EXPONENT {
+                return None;
+            }
+            raw |= u64::from(*exponent) << (4 * idx);
+        }
+        Some(BitBoard(raw))
+    }
+
+    /// Get the exponent of the tile at the given index
+    pub fn get_exponent(&self, index: usize) -> u8 {
+        ((self.0 >> (4 * index)) & 0xf) as u8
+    }
+
+    /// Get tile values as exponents, e.g. 11 for the tile 2048 and 0 for an empty tile
+    pub fn get_exponents(&self) -> [u8; 16] {
+        let mut exponents = [0; 16];
+        for (idx, exponent) in exponents.iter_mut().enumerate() {
+            *exponent = self.get_exponent(idx);
+        }
+        exponents
+    }
+
+    /// Get tile values
+    pub fn get_values(&self) -> Vec<u32> {
+        self.get_exponents().iter().copied().map(exponent_to_value).collect()
+    }
+
+    /// Set a single tile by the given exponent
+    pub fn set_exponent(&mut self, index: usize, exponent: u8) {
+        let shift = 4 * index;
+        self.0 = (self.0 & !(0xf << shift)) | (u64::from(exponent & 0xf) << shift);
+    }
+
+    /// Get the indexes of all empty tiles
+    pub fn empty_tiles(&self) -> Vec<usize> {
+        (0..16).filter(|idx| self.get_exponent(*idx) == 0).collect()
+    }
+
+    /// Count the empty tiles without unpacking the board
+    pub fn count_empty(&self) -> u32 {
+        // set the lowest bit of a nibble if any bit of it is set, then count the zero nibbles
+        let mut x = self.0;
+        x |= (x >> 2) & 0x3333_3333_3333_3333;
+        x |= x >> 1;
+        16 - (x & 0x1111_1111_1111_1111).count_ones()
+    }
+
+    /// Get the largest tile value on the board
+    pub fn max_tile(&self) -> u32 {
+        exponent_to_value(self.get_exponents().iter().copied().max().unwrap_or(0))
+    }
+
+    /// Transpose the tiles, so rows become columns
+    pub fn transpose(&self) -> Self {
+        let x = self.0;
+        let a1 = x & 0xF0F0_0F0F_F0F0_0F0F;
+        let a2 = x & 0x0000_F0F0_0000_F0F0;
+        let a3 = x & 0x0F0F_0000_0F0F_0000;
+        let a = a1 | (a2 << 12) | (a3 >> 12);
+        let b1 = a & 0xFF00_FF00_00FF_00FF;
+        let b2 = a & 0x00FF_00FF_0000_0000;
+        let b3 = a & 0x0000_0000_FF00_FF00;
+        BitBoard(b1 | (b2 >> 24) | (b3 << 24))
+    }
+
+    /// Move all rows by the given lookup table, and return the outcome of the movement
+    fn move_rows(&mut self, table: &[u16]) -> MoveInfo {
+        let tables = tables();
+        let mut info = MoveInfo::default();
+        let mut raw = 0;
+        for r in 0..4 {
+            let row = ((self.0 >> (16 * r)) & 0xffff) as usize;
+            raw |= u64::from(table[row]) << (16 * r);
+            info.score += tables.score[row];
+            info.merges += u32::from(tables.merges[row]);
+        }
+        info.moved = raw != self.0;
+        self.0 = raw;
+        info
+    }
+
+    /// Move up all tiles, and return the outcome of the movement
+    pub fn move_up(&mut self) -> MoveInfo {
+        let mut transposed = self.transpose();
+        let info = transposed.move_rows(&tables().left);
+        *self = transposed.transpose();
+        info
+    }
+
+    /// Move down all tiles, and return the outcome of the movement
+    pub fn move_down(&mut self) -> MoveInfo {
+        let mut transposed = self.transpose();
+        let info = transposed.move_rows(&tables().right);
+        *self = transposed.transpose();
+        info
+    }
+
+    /// Move left all tiles, and return the outcome of the movement
+    pub fn move_left(&mut self) -> MoveInfo {
+        self.move_rows(&tables().left)
+    }
+
+    /// Move right all tiles, and return the outcome of the movement
+    pub fn move_right(&mut self) -> MoveInfo {
+        self.move_rows(&tables().right)
+    }
+
+    /// Move all tiles toward the given direction, and return the outcome of the movement
+    ///
+    /// # Arguments
+    /// * `dir` The direction of tile movement
+    ///
+    pub fn move_tiles(&mut self, dir: Direction) -> MoveInfo {
+        match dir {
+            Direction::Up => self.move_up(),
+            Direction::Down => self.move_down(),
+            Direction::Left => self.move_left(),
+            Direction::Right => self.move_right(),
+        }
+    }
+
+    /// Get a new board with all tiles moved toward the given direction, and the outcome of the
+    /// movement. Returns `None` if no tile is moved.
+    ///
+    /// # Arguments
+    /// * `dir` The direction of tile movement
+    ///
+    pub fn apply(&self, dir: Direction) -> Option<(BitBoard, MoveInfo)> {
+        let mut board = *self;
+        let info = board.move_tiles(dir);
+        if info.moved {
+            Some((board, info))
+        } else {
+            None
+        }
+    }
+
+    /// Get the directions that move any tile, in the order of left, right, up and down
+    pub fn legal_moves(&self) -> Vec<Direction> {
+        Direction::ALL.iter()
+            .copied()
+            .filter(|dir| self.apply(*dir).is_some())
+            .collect()
+    }
+
+    /// Check whether any movement is still possible
+    pub fn can_move(&self) -> bool {
+        let mut board = *self;
+        board.move_left().moved || board.move_up().moved
+            || board.move_right().moved || board.move_down().moved
+    }
+}
+
+impl From<BitBoard> for Board {
+    fn from(board: BitBoard) -> Self {
+        Board::new(board.get_values())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::{move_row_left, move_row_right};
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_pack_row() {
+        for row in 0..=u16::MAX {
+            assert_eq!(pack_row(unpack_row(row)), row);
+        }
+    }
+
+    #[test]
+    fn test_row_tables() {
+        // every row without the 32768 tile is the same as the `Board` row movement
+        let tables = tables();
+        for row in 0..=u16::MAX {
+            let tiles = unpack_row(row);
+            if tiles.contains(&MAX_BIT_EXPONENT) {
+                continue;
+            }
+            let (left, info) = move_row_left(&tiles);
+            assert_eq!(unpack_row(tables.left[row as usize]).to_vec(), left);
+            assert_eq!(tables.score[row as usize], info.score);
+            assert_eq!(u32::from(tables.merges[row as usize]), info.merges);
+            let (right, _) = move_row_right(&tiles);
+            assert_eq!(unpack_row(tables.right[row as usize]).to_vec(), right);
+        }
+    }
+
+    #[test]
+    fn test_not_merge_max_exponent() {
+        let tables = tables();
+        let row = pack_row([15, 15, 0, 0]);
+        assert_eq!(tables.left[row as usize], row);
+        assert_eq!(tables.score[row as usize], 0);
+    }
+
+    #[test]
+    fn test_conversion() {
+        let values = vec![
+                0, 2, 512, 16,
+                0, 2, 8, 16,
+                4, 4, 2, 32,
+                2, 0, 4, 32768,
+            ];
+        let board = Board::new(values.clone());
+        let bitboard = BitBoard::from_board(&board).unwrap();
+        assert_eq!(bitboard.get_values(), values);
+        assert_eq!(bitboard.get_exponent(15), 15);
+        assert_eq!(Board::from(bitboard).get_values(), values);
+
+        assert_eq!(BitBoard::from_board(&Board::new(vec![0; 9])), None);
+        let mut values = values;
+        values[0] = 65536;
+        assert_eq!(BitBoard::from_board(&Board::new(values)), None);
+    }
+
+    #[test]
+    fn test_transpose() {
+        let values: Vec<u32> = (0..16).map(|i| if i == 0 {0} else {1 << i}).collect();
+        let bitboard = BitBoard::from_board(&Board::new(values.clone())).unwrap();
+        let transposed = bitboard.transpose();
+        for r in 0..4 {
+            for c in 0..4 {
+                assert_eq!(transposed.get_values()[c * 4 + r], values[r * 4 + c]);
+            }
+        }
+        assert_eq!(transposed.transpose(), bitboard);
+    }
+
+    #[test]
+    fn test_set_exponent_and_empty() {
+        let mut bitboard = BitBoard::default();
+        assert_eq!(bitboard.count_empty(), 16);
+        bitboard.set_exponent(3, 11);
+        bitboard.set_exponent(9, 1);
+        assert_eq!(bitboard.count_empty(), 14);
+        assert_eq!(bitboard.empty_tiles().len(), 14);
+        assert_eq!(bitboard.max_tile(), 2048);
+        bitboard.set_exponent(3, 0);
+        assert_eq!(bitboard.get_exponent(3), 0);
+        assert_eq!(bitboard.count_empty(), 15);
+    }
+
+    #[test]
+    fn test_apply() {
+        let board: Board = "2,2,0,0/0,4,0,0/0,0,0,0/0,0,0,8".parse().unwrap();
+        let bitboard = BitBoard::from_board(&board).unwrap();
+        assert_eq!(bitboard.legal_moves(), board.legal_moves());
+        for dir in Direction::ALL.iter() {
+            let (moved, info) = bitboard.apply(*dir).unwrap();
+            let (expected, expected_info) = board.apply(*dir).unwrap();
+            assert_eq!(Board::from(moved), expected);
+            assert_eq!(info, expected_info);
+        }
+        let full = BitBoard::from_board(&"2,4,2,4/4,2,4,2/2,4,2,4/4,2,4,2".parse().unwrap()).unwrap();
+        assert_eq!(full.apply(Direction::Left), None);
+        assert!(full.legal_moves().is_empty());
+    }
+
+    type BoardMove = fn(&mut Board) -> MoveInfo;
+    type BitBoardMove = fn(&mut BitBoard) -> MoveInfo;
+
+    #[test]
+    fn test_move_against_board() {
+        // random boards are moved toward every direction by both `Board` and `BitBoard`
+        let mut rng = ChaCha8Rng::seed_from_u64(2048);
+        for _ in 0..5000 {
+            let values: Vec<u32> = (0..16)
+                .map(|_| if rng.gen_bool(0.3) {0} else {1 << rng.gen_range(1..=14)})
+                .collect();
+            let moves: [(BoardMove, BitBoardMove); 4] = [
+                (Board::move_left, BitBoard::move_left),
+                (Board::move_right, BitBoard::move_right),
+                (Board::move_up, BitBoard::move_up),
+                (Board::move_down, BitBoard::move_down),
+            ];
+            let origin = Board::new(values);
+            for (board_move, bitboard_move) in moves.iter() {
+                let mut board = origin.clone();
+                let mut bitboard = BitBoard::from_board(&board).unwrap();
+                assert_eq!(board_move(&mut board), bitboard_move(&mut bitboard));
+                assert_eq!(board.get_values(), bitboard.get_values());
+            }
+            let bitboard = BitBoard::from_board(&origin).unwrap();
+            assert_eq!(origin.can_move(), bitboard.can_move());
+            assert_eq!(origin.empty_tiles().len() as u32, bitboard.count_empty());
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use crate::tile::Board;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// `Evaluator` evaluates a board from the player's view, a larger value is better. The AI
+/// (see `Solver`) is parameterized over the evaluator, so the evaluation can be changed or
+/// tuned without touching the search.
+///
+/// An evaluation may be negative. A board without any movement is not evaluated, the search
+/// values it `LOSS_VALUE` instead.
+pub trait Evaluator {
+    /// Evaluate the given board
+    ///
+    /// # Arguments
+    /// * `board` The board to evaluate
+    ///
+    fn evaluate(&self, board: &Board) -> f64;
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for Box<E> {
+    fn evaluate(&self, board: &Board) -> f64 {
+        (**self).evaluate(board)
+    }
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for &E {
+    fn evaluate(&self, board: &Board) -> f64 {
+        (**self).evaluate(board)
+    }
+}
+
+/// Get all rows and columns of the board as exponents
+fn lines(board: &Board) -> Vec<Vec<u8>> {
+    let exponents = board.get_exponents();
+    let (rows, cols) = (board.get_rows(), board.get_cols());
+    let mut lines: Vec<Vec<u8>> = exponents.chunks(cols).map(|row| row.to_vec()).collect();
+    lines.extend((0..cols).map(|col| (0..rows).map(|row| exponents[row * cols + col]).collect()));
+    lines
+}
+
+/// The number of empty tiles
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmptyTiles;
+
+impl Evaluator for EmptyTiles {
+    fn evaluate(&self, board: &Board) -> f64 {
+        board.empty_tiles().len() as f64
+    }
+}
+
+/// How far rows and columns are from being monotonic, as a negative value. For each line,
+/// the exponent differences of the increasing and the decreasing neighbors are summed, and
+/// the smaller one is the penalty.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Monotonicity;
+
+impl Evaluator for Monotonicity {
+    fn evaluate(&self, board: &Board) -> f64 {
+        let penalty: u32 = lines(board).iter()
+            .map(|line| {
+                let (mut increase, mut decrease) = (0, 0);
+                for pair in line.windows(2) {
+                    let (a, b) = (u32::from(pair[0]), u32::from(pair[1]));
+                    if a > b {decrease += a - b} else {increase += b - a}
+                }
+                increase.min(decrease)
+            })
+            .sum();
+        -f64::from(penalty)
+    }
+}
+
+/// How different the neighboring tiles are, as a negative value, which is the sum of the
+/// exponent differences of neighboring tiles. Empty tiles are skipped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Smoothness;
+
+impl Evaluator for Smoothness {
+    fn evaluate(&self, board: &Board) -> f64 {
+        let penalty: u32 = lines(board).iter()
+            .map(|line| {
+                let tiles: Vec<u32> = line.iter()
+                    .filter(|exponent| **exponent != 0)
+                    .map(|exponent| u32::from(*exponent))
+                    .collect();
+                tiles.windows(2).map(|pair| pair[0].max(pair[1]) - pair[0].min(pair[1])).sum::<u32>()
+            })
+            .sum();
+        -f64::from(penalty)
+    }
+}
+
+/// How large tiles gather in a corner. Each tile exponent is weighted by its closeness to the
+/// corner, and the best corner is taken.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CornerWeight;
+
+impl Evaluator for CornerWeight {
+    fn evaluate(&self, board: &Board) -> f64 {
+        let (rows, cols) = (board.get_rows(), board.get_cols());
+        let farthest = rows + cols - 2;
+        let corners = [(0, 0), (0, cols - 1), (rows - 1, 0), (rows - 1, cols - 1)];
+        corners.iter()
+            .map(|(corner_row, corner_col)| {
+                board.get_exponents().iter().enumerate()
+                    .map(|(idx, exponent)| {
+                        let (row, col) = (idx / cols, idx % cols);
+                        let distance = row.max(*corner_row) - row.min(*corner_row)
+                            + col.max(*corner_col) - col.min(*corner_col);
+                        (farthest - distance) * usize::from(*exponent)
+                    })
+                    .sum::<usize>()
+            })
+            .max()
+            .unwrap_or(0) as f64
+    }
+}
+
+/// The number of neighboring tiles with the same value, which can be merged by the next movement
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MergePotential;
+
+impl Evaluator for MergePotential {
+    fn evaluate(&self, board: &Board) -> f64 {
+        lines(board).iter()
+            .map(|line| line.windows(2).filter(|pair| pair[0] != 0 && pair[0] == pair[1]).count())
+            .sum::<usize>() as f64
+    }
+}
+
+/// The default heuristic of `Solver`, which combines empty tiles, merges, monotonicity and
+/// the tile sum per row and column with tuned powers and weights. It becomes negative once
+/// large tiles appear, since the tile sum is a penalty.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LineHeuristic;
+
+impl LineHeuristic {
+    /// Evaluate a row or a column of exponents
+    fn evaluate_line(line: &[u8]) -> f64 {
+        let empty = line.iter().filter(|exponent| **exponent == 0).count() as f64;
+        let mut merges = 0.0;
+        let mut prev = 0;
+        let mut counter = 0.0;
+        let mut sum = 0.0;
+        for exponent in line.iter().copied().filter(|exponent| *exponent != 0) {
+            sum += f64::from(exponent).powf(3.5);
+            if exponent == prev {
+                counter += 1.0;
+            } else if counter > 0.0 {
+                merges += 1.0 + counter;
+                counter = 0.0;
+            }
+            prev = exponent;
+        }
+        if counter > 0.0 {
+            merges += 1.0 + counter;
+        }
+
+        let mut monotonic_left = 0.0;
+        let mut monotonic_right = 0.0;
+        for pair in line.windows(2) {
+            let (a, b) = (f64::from(pair[0]).powi(4), f64::from(pair[1]).powi(4));
+            if a > b {
+                monotonic_left += a - b;
+            } else {
+                monotonic_right += b - a;
+            }
+        }
+
+        200000.0 + 270.0 * empty + 700.0 * merges - 47.0 * monotonic_left.min(monotonic_right) - 11.0 * sum
+    }
+}
+
+impl Evaluator for LineHeuristic {
+    fn evaluate(&self, board: &Board) -> f64 {
+        lines(board).iter().map(|line| Self::evaluate_line(line)).sum()
+    }
+}
+
+/// Get the built-in evaluator by the name, which is used in weight files
+///
+/// * `empty` see `EmptyTiles`
+/// * `monotonicity` see `Monotonicity`
+/// * `smoothness` see `Smoothness`
+/// * `corner` see `CornerWeight`
+/// * `merges` see `MergePotential`
+/// * `lines` see `LineHeuristic`
+pub fn builtin(name: &str) -> Option<Box<dyn Evaluator + Send>> {
+    match name {
+        "empty" => Some(Box::new(EmptyTiles)),
+        "monotonicity" => Some(Box::new(Monotonicity)),
+        "smoothness" => Some(Box::new(Smoothness)),
+        "corner" => Some(Box::new(CornerWeight)),
+        "merges" => Some(Box::new(MergePotential)),
+        "lines" => Some(Box::new(LineHeuristic)),
+        _ => None,
+    }
+}
+
+/// Errors of loading weights
+///
+/// * `Io` The file can not be read, with the reason
+/// * `InvalidLine` A line is malformed, with the line number (from 1) and the reason
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WeightsError {
+    Io(String),
+    InvalidLine { line: usize, reason: String },
+}
+
+impl Display for WeightsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            WeightsError::Io(reason) => write!(f, "{}", reason),
+            WeightsError::InvalidLine { line, reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
+
+/// The weighted sum of evaluators with a constant bias
+///
+/// Weights can be loaded from a text file, so they can be tuned without recompiling. Each line
+/// is the name of a built-in evaluator (see `builtin`) or `bias`, and the weight separated by
+/// spaces. Empty lines and lines starting with `#` are ignored. For example,
+///
+/// ```text
+/// # weights for a 4x4 board
+/// bias 1000
+/// empty 270
+/// monotonicity 47
+/// smoothness 10
+/// ```
+#[derive(Default)]
+pub struct Weighted {
+    bias: f64,
+    terms: Vec<(f64, Box<dyn Evaluator + Send>)>,
+}
+
+impl Weighted {
+    /// Create a new `Weighted` without any evaluator, which evaluates every board as the bias
+    ///
+    /// # Arguments
+    /// * `bias` The constant added to the weighted sum
+    ///
+    pub fn new(bias: f64) -> Self {
+        Weighted { bias, terms: Vec::new() }
+    }
+
+    /// Add an evaluator with the given weight
+    ///
+    /// # Arguments
+    /// * `weight` The weight of the evaluator
+    /// * `evaluator` The evaluator
+    ///
+    pub fn add<E: Evaluator + Send + 'static>(&mut self, weight: f64, evaluator: E) {
+        self.terms.push((weight, Box::new(evaluator)));
+    }
+
+    /// Parse weights of built-in evaluators from the text of a weight file
+    ///
+    /// # Arguments
+    /// * `text` The weights
+    ///
+    pub fn from_weights(text: &str) -> Result<Self, WeightsError> {
+        let mut weighted = Weighted::new(0.0);
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: String| WeightsError::InvalidLine { line: idx + 1, reason };
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let (name, weight) = match parts.as_slice() {
+                [name, weight] => (*name, *weight),
+                _ => return Err(invalid(format!("expected `<name> <weight>`, found `{}`", line))),
+            };
+            let weight: f64 = weight.parse().map_err(|_| invalid(format!("invalid weight `{}`", weight)))?;
+            if name == "bias" {
+                weighted.bias = weight;
+            } else {
+                let evaluator = builtin(name).ok_or_else(|| invalid(format!("unknown evaluator `{}`", name)))?;
+                weighted.terms.push((weight, evaluator));
+            }
+        }
+        Ok(weighted)
+    }
+
+    /// Load weights of built-in evaluators from a weight file, see `from_weights`
+    ///
+    /// # Arguments
+    /// * `path` The path of the weight file
+    ///
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WeightsError> {
+        let text = std::fs::read_to_string(path).map_err(|err| WeightsError::Io(err.to_string()))?;
+        Self::from_weights(&text)
+    }
+}
+
+impl Evaluator for Weighted {
+    fn evaluate(&self, board: &Board) -> f64 {
+        self.bias + self.terms.iter().map(|(weight, evaluator)| weight * evaluator.evaluate(board)).sum::<f64>()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_evaluators() {
+        // exponents are 1,3,2/2,0,4/0,0,5
+        let board: Board = "2,8,4/4,0,16/0,0,32".parse().unwrap();
+        assert_eq!(EmptyTiles.evaluate(&board), 3.0);
+        // rows 1, 2, 0 and columns 1, 0, 0
+        assert_eq!(Monotonicity.evaluate(&board), -4.0);
+        // rows 2+1, 2, 0 and columns 1, 0, 2+1
+        assert_eq!(Smoothness.evaluate(&board), -9.0);
+        // the top right corner is the best
+        assert_eq!(CornerWeight.evaluate(&board), 43.0);
+        assert_eq!(MergePotential.evaluate(&board), 0.0);
+        assert_eq!(MergePotential.evaluate(&"2,2/0,0".parse().unwrap()), 1.0);
+        assert!(LineHeuristic.evaluate(&board) > 0.0);
+
+        let board: Board = "0,0/0,0".parse().unwrap();
+        assert_eq!(EmptyTiles.evaluate(&board), 4.0);
+        assert_eq!(Monotonicity.evaluate(&board), 0.0);
+        assert_eq!(CornerWeight.evaluate(&board), 0.0);
+    }
+
+    #[test]
+    fn test_weighted() {
+        let board: Board = "2,8,4/4,0,16/0,0,32".parse().unwrap();
+        let mut weighted = Weighted::new(100.0);
+        assert_eq!(weighted.evaluate(&board), 100.0);
+        weighted.add(10.0, EmptyTiles);
+        weighted.add(2.0, Monotonicity);
+        assert_eq!(weighted.evaluate(&board), 122.0);
+
+        let text = "# comment\n\nbias 100\nempty 10\nmonotonicity 2\n";
+        assert_eq!(Weighted::from_weights(text).unwrap().evaluate(&board), 122.0);
+        assert_eq!(Weighted::from_weights("empty x").err(),
+            Some(WeightsError::InvalidLine { line: 1, reason: "invalid weight `x`".to_string() }));
+        assert_eq!(Weighted::from_weights("bias 1\ncorners 1").err().unwrap().to_string(),
+            "line 2: unknown evaluator `corners`");
+        assert!(Weighted::from_weights("empty").is_err());
+    }
+
+    #[test]
+    fn test_weighted_load() {
+        let path = std::env::temp_dir().join("wasm_terminal_2048_test_weights");
+        std::fs::write(&path, "bias 5\nmerges 3\n").unwrap();
+        let weighted = Weighted::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(weighted.evaluate(&"2,2/0,0".parse().unwrap()), 8.0);
+        assert!(matches!(Weighted::load(&path), Err(WeightsError::Io(_))));
+    }
+}
@@ -1,10 +1,24 @@
-use crate::tile::{Board, Direction};
+use crate::save::{LoadError, SaveData};
+use crate::spawn::{ClassicSpawn, SpawnPolicy};
+use crate::tile::{Board, Direction, MoveInfo};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
 
 /// wasm-terminal-2048
 ///
 /// wasm-terminal-2048 library  is composed of `tile` module and `game` module. The `tile` has `Board`
-/// struct that store 16 tile values and can move tiles toward four directions. The other `game`
+/// struct that store tile values of a board (4x4 by default) and can move tiles toward four directions. The other `game`
 /// struct includes `Game` struct that is responsible for high-level logic that is the bridge between the front-end, such as a command line terminal or a browser application, and the back-end game logic. 
+/// The `spawn` module has the `SpawnPolicy` trait and its implementations that decide how new tiles are generated.
+/// The `save` module has the text format of `Game::save` and `Game::load`.
+/// The `bitboard` module packs a 4x4 board in 64 bits, and the `symmetry` module has the rotations and reflections
+/// of a board, both are for AI search.
+/// The `solver` module has the expectimax `Solver` that finds the best movement of a board, which is evaluated by
+/// the `Evaluator` of the `evaluator` module. The `rollout` module has the `RolloutAgent` that finds the best movement
+/// by random or greedy playouts. The `agent` module has the `Agent` trait that chooses the movement of a game, which
+/// is implemented by the AIs and the baseline strategies. The `ntuple` module has the `NTupleNetwork` that is trained by
+/// the temporal difference learning of `TdTrainer`.
 ///
 /// `Game` is used to control game iterations, execute user actions and update internal tile values in the board. The example is as below. The complete example can be found in `cli/src/main.rs` for command line terminal or `wasm/src/lib.rs` for webassembly
 ///
@@ -16,34 +30,162 @@ use crate::tile::{Board, Direction};
 ///  // call render function depends on the front-end
 ///  render()
 ///
-///  // looping: process user input and execute an action 
+///  // looping: process user input and execute an action. A new tile is generated
+///  // only if the action moved any tile
 ///  for c in stdin.keys() {
-///    match c.unwrap() {
+///    let info = match c.unwrap() {
 ///      Key::Down |  Key::Char('j') => game.action(Direction::Down), 
 ///      _ => continue,
-///    }
+///    };
 ///
-///    // move to next iteration, and render the updated board
-///    game.next();
-///    render(&mut stdout, &game);
+///    // render the updated board
+///    if info.moved {
+///      render(&mut stdout, &game);
+///    }
 ///  }
 ///
 /// ```
+///
+/// `Game` is parameterized over the `SpawnPolicy` that generates new tiles, which is the classic
+/// rule `ClassicSpawn` by default.
+///
+/// ```ignore
+///  // a game that generates the worst tile for the player
+///  let mut game = Game::with_policy(Difficulty::Expert.policy());
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(serialize = "", deserialize = "P: Default")))]
+#[cfg_attr(feature = "serde", serde(try_from = "GameData"))]
+pub struct Game<P: SpawnPolicy = ClassicSpawn> {
+    board: Board,
+    // the policy is not serialized, and the default policy is used after deserialization
+    #[cfg_attr(feature = "serde", serde(skip))]
+    policy: P,
+    rng: ChaCha8Rng,
+    seed: u64,
+    next_seed: Option<u64>,
+    steps: u32,
+    score: u32,
+    best_score: u32,
+    status: GameStatus,
+    undo_history: VecDeque<Snapshot>,
+    redo_history: Vec<Snapshot>,
+    history_size: usize,
+    undo_limit: Option<u32>,
+    undos_left: Option<u32>,
+}
 
-pub struct Game {
+/// The fields of a deserialized `Game`, which are checked as a loaded save, see `Game::load`
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct GameData {
     board: Board,
+    rng: ChaCha8Rng,
+    seed: u64,
+    next_seed: Option<u64>,
     steps: u32,
+    score: u32,
+    best_score: u32,
+    status: GameStatus,
+    undo_history: Vec<Snapshot>,
+    redo_history: Vec<Snapshot>,
+    history_size: usize,
+    undo_limit: Option<u32>,
+    undos_left: Option<u32>,
+}
+
+#[cfg(feature = "serde")]
+impl<P: SpawnPolicy + Default> std::convert::TryFrom<GameData> for Game<P> {
+    type Error = LoadError;
+
+    fn try_from(data: GameData) -> Result<Self, Self::Error> {
+        let save = SaveData {
+            seed: data.seed,
+            best_score: data.best_score,
+            undo_limit: data.undo_limit,
+            undos_left: data.undos_left,
+            history_size: data.history_size,
+            current: Snapshot {
+                board: data.board,
+                rng: data.rng,
+                steps: data.steps,
+                score: data.score,
+                status: data.status,
+            },
+            undo_history: data.undo_history,
+            redo_history: data.redo_history,
+        };
+        save.validate()?;
+        let mut game = Self::with_save_data(save, P::default());
+        game.next_seed = data.next_seed;
+        Ok(game)
+    }
+}
+
+/// The tile value that wins the game
+pub const WIN_TILE: u32 = 2048;
+
+/// The default number of actions that can be undone
+pub const DEFAULT_HISTORY_SIZE: usize = 100;
+
+/// A snapshot of the game state for undo and redo. The random generator is included, so the
+/// same tile is generated again after redo.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Snapshot {
+    pub(crate) board: Board,
+    pub(crate) rng: ChaCha8Rng,
+    pub(crate) steps: u32,
+    pub(crate) score: u32,
+    pub(crate) status: GameStatus,
+}
+
+/// Status of a game, which is updated by `Game` after each action
+///
+/// * `Playing` The game is in progress
+/// * `Won` The `WIN_TILE` is reached. Actions are ignored until `Game::keep_going` is called
+/// * `WonContinuing` The game is won, and the player keeps going after reaching the `WIN_TILE`
+/// * `Lost` No more movement is possible
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameStatus {
+    Playing, Won, WonContinuing, Lost
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Game {
 
-    /// Create a new board with the default tile values 0
+    /// Create a new board with the default tile values 0. The first game is seeded by the OS.
     pub fn new() -> Self {
-        let board: Board = Default::default();
-        Self {
-            board: board,
-            steps: 0
-        }
+        Self::with_seed(rand::random())
+    }
+
+    /// Create a new board with the default tile values 0. The first game started by
+    /// `start_game` is seeded by the given seed, so it can be reproduced exactly.
+    ///
+    /// # Arguments
+    /// * `seed` The seed of the first game
+    ///
+    pub fn with_seed(seed: u64) -> Self {
+        Self::create(ClassicSpawn, seed)
+    }
+
+    /// Create a new board with the given number of rows and columns. The first game is seeded
+    /// by the OS.
+    ///
+    /// # Arguments
+    /// * `rows` The number of rows
+    /// * `cols` The number of columns
+    ///
+    pub fn with_size(rows: usize, cols: usize) -> Self {
+        let mut game = Self::new();
+        game.set_size(rows, cols);
+        game
     }
 
     /// Create a new board by a given tile values  (Only for testing)
@@ -52,31 +194,303 @@ impl Game {
     /// * `values` Tile values
     ///
     #[cfg(test)]
-    fn new_with_values(values: Vec<u16>) -> Self {
+    pub(crate) fn new_with_values(values: Vec<u32>) -> Self {
+        let mut game = Self::create(ClassicSpawn, 0);
+        game.board = Board::new(values);
+        game.next_seed = None;
+        game
+    }
+
+    /// Create a new board with the default tile values 0. The first game is seeded by a seed
+    /// drawn from the given random generator.
+    ///
+    /// # Arguments
+    /// * `rng` The random generator to draw the seed from
+    ///
+    pub fn from_rng<R: RngCore + ?Sized>(rng: &mut R) -> Self {
+        Self::with_seed(rng.next_u64())
+    }
+}
+
+impl<P: SpawnPolicy> Game<P> {
+
+    /// Create a new board with the default tile values 0, and new tiles are generated by the
+    /// given spawn policy. The first game is seeded by the OS, use `start_game_with_seed` to
+    /// start a reproducible game.
+    ///
+    /// # Arguments
+    /// * `policy` The spawn policy of new tiles
+    ///
+    pub fn with_policy(policy: P) -> Self {
+        Self::create(policy, rand::random())
+    }
+
+    /// Create a new board with the default tile values 0, the first game is seeded by the given seed
+    fn create(policy: P, seed: u64) -> Self {
         Self {
-            board: Board::new(values),
+            board: Board::default(),
+            policy,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            seed,
+            next_seed: Some(seed),
             steps: 0,
+            score: 0,
+            best_score: 0,
+            status: GameStatus::Playing,
+            undo_history: VecDeque::new(),
+            redo_history: Vec::new(),
+            history_size: DEFAULT_HISTORY_SIZE,
+            undo_limit: None,
+            undos_left: None,
         }
     }
 
-    /// This method resets the internal tile values, and start a new game
+    /// Change the number of rows and columns of the board. The board is cleared, and
+    /// `start_game` should be called to start a new game with the new size.
+    ///
+    /// # Arguments
+    /// * `rows` The number of rows
+    /// * `cols` The number of columns
+    ///
+    pub fn set_size(&mut self, rows: usize, cols: usize) {
+        self.board = Board::with_size(rows, cols, vec![0; rows * cols]);
+        self.rng = ChaCha8Rng::seed_from_u64(self.seed);
+        self.reset_game();
+    }
+
+    /// Get the spawn policy
+    pub fn get_policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// Change the spawn policy, which is applied from the next generated tile
+    pub fn set_policy(&mut self, policy: P) {
+        self.policy = policy;
+    }
+
+    /// This method resets the internal tile values, and start a new game. The first game uses
+    /// the seed given at creation, and the following games are seeded by the OS.
     pub fn start_game(&mut self) {
+        let seed = self.next_seed.take().unwrap_or_else(rand::random);
+        self.start_game_with_seed(seed);
+    }
+
+    /// This method resets the internal tile values, and start a new game seeded by the given seed
+    ///
+    /// # Arguments
+    /// * `seed` The seed of the new game
+    ///
+    pub fn start_game_with_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.next_seed = None;
+        let (rows, cols) = (self.board.get_rows(), self.board.get_cols());
+        self.board = Board::with_size(rows, cols, vec![0; rows * cols]);
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
         self.reset_game();
-        self.board.generate_new_tile();
+        self.policy.spawn(&mut self.board, &mut self.rng);
+    }
+
+    /// Get the seed of the current game. Starting a game with this seed and applying the same
+    /// actions reproduces the current game exactly.
+    pub fn get_seed(&self) -> u64 {
+        self.seed
     }
     
 
-    /// By a given direction, updates the internal tile values after the movement
+    /// By a given direction, updates the internal tile values after the movement. If any tile
+    /// is moved, adds the merged tile values to the score and moves to the next iteration, which
+    /// generates a new tile and increments steps. Returns the outcome of the movement.
+    /// Actions are ignored when the game is won (until `keep_going`) or lost.
     ///
     /// # Arguments
     /// * `Direction` The direction of tile movement  
     ///
-    pub fn action(&mut self, dir: Direction) {
-        match dir {
-            Direction::Up => self.board.move_up(),
-            Direction::Down => self.board.move_down(), 
-            Direction::Left => self.board.move_left(), 
-            Direction::Right => self.board.move_right(), 
+    pub fn action(&mut self, dir: Direction) -> MoveInfo {
+        if !self.accepts_action() {
+            return MoveInfo::default();
+        }
+        let snapshot = self.snapshot();
+        let info = self.board.move_tiles(dir);
+        if info.moved {
+            self.push_undo(snapshot);
+            self.redo_history.clear();
+            self.score += info.score;
+            self.best_score = self.best_score.max(self.score);
+            self.next();
+        }
+        info
+    }
+
+    /// Take back the last action. Returns false if there is no action to undo, or no undo is
+    /// left when the number of undos is limited.
+    pub fn undo(&mut self) -> bool {
+        if !self.can_undo() {
+            return false;
+        }
+        let snapshot = self.undo_history.pop_back().unwrap();
+        self.redo_history.push(self.snapshot());
+        self.restore(snapshot);
+        if let Some(left) = self.undos_left.as_mut() {
+            *left -= 1;
+        }
+        true
+    }
+
+    /// Apply the last undone action again, which generates the same new tile. Returns false if
+    /// there is no action to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo_history.pop() {
+            Some(snapshot) => {
+                let current = self.snapshot();
+                self.push_undo(current);
+                self.restore(snapshot);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Check whether any action can be undone
+    pub fn can_undo(&self) -> bool {
+        !self.undo_history.is_empty() && self.undos_left != Some(0)
+    }
+
+    /// Check whether any undone action can be redone
+    pub fn can_redo(&self) -> bool {
+        !self.redo_history.is_empty()
+    }
+
+    /// Limit the number of undos per game, `None` means unlimited. The limit is applied from
+    /// the current game.
+    ///
+    /// # Arguments
+    /// * `limit` The number of undos per game
+    ///
+    pub fn set_undo_limit(&mut self, limit: Option<u32>) {
+        self.undo_limit = limit;
+        self.undos_left = limit;
+    }
+
+    /// Get the number of undos left in the current game, `None` means unlimited
+    pub fn get_undos_left(&self) -> Option<u32> {
+        self.undos_left
+    }
+
+    /// Set the number of actions kept in the history for undo and redo, the actions farthest
+    /// from the current state are dropped
+    ///
+    /// # Arguments
+    /// * `size` The number of actions that can be undone
+    ///
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history_size = size;
+        while self.undo_history.len() > size {
+            self.undo_history.pop_front();
+        }
+        // the last redo snapshot is the next one to redo
+        let excess = self.redo_history.len().saturating_sub(size);
+        self.redo_history.drain(..excess);
+    }
+
+    /// Take a snapshot of the current game state
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            board: self.board.clone(),
+            rng: self.rng.clone(),
+            steps: self.steps,
+            score: self.score,
+            status: self.status,
+        }
+    }
+
+    /// Restore the game state by the given snapshot. The best score is kept.
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.board = snapshot.board;
+        self.rng = snapshot.rng;
+        self.steps = snapshot.steps;
+        self.score = snapshot.score;
+        self.status = snapshot.status;
+    }
+
+    /// Push a snapshot to the undo history, and drop the oldest one if the history is full
+    fn push_undo(&mut self, snapshot: Snapshot) {
+        if self.history_size == 0 {
+            return;
+        }
+        if self.undo_history.len() == self.history_size {
+            self.undo_history.pop_front();
+        }
+        self.undo_history.push_back(snapshot);
+    }
+
+    /// Save the game to a versioned, human-readable text, which includes the board, score,
+    /// steps, random generator, undo history and settings. The spawn policy is not saved.
+    pub fn save(&self) -> String {
+        SaveData {
+            seed: self.seed,
+            best_score: self.best_score,
+            undo_limit: self.undo_limit,
+            undos_left: self.undos_left,
+            history_size: self.history_size,
+            current: self.snapshot(),
+            undo_history: self.undo_history.iter().cloned().collect(),
+            redo_history: self.redo_history.clone(),
+        }.to_text()
+    }
+
+    /// Load a game saved by `save`. The game continues exactly as the saved one, e.g. the same
+    /// tiles are generated by the same actions.
+    ///
+    /// # Arguments
+    /// * `text` The saved game
+    /// * `policy` The spawn policy of new tiles
+    ///
+    pub fn load(text: &str, policy: P) -> Result<Self, LoadError> {
+        Ok(Self::with_save_data(SaveData::parse(text)?, policy))
+    }
+
+    /// Create the game of the checked save data, which continues exactly as the saved one
+    fn with_save_data(data: SaveData, policy: P) -> Self {
+        let mut game = Self::create(policy, data.seed);
+        game.next_seed = None;
+        game.best_score = data.best_score;
+        game.undo_limit = data.undo_limit;
+        game.undos_left = data.undos_left;
+        game.history_size = data.history_size;
+        game.restore(data.current);
+        game.undo_history = data.undo_history.into();
+        game.redo_history = data.redo_history;
+        game
+    }
+
+    /// Get the status of the current game
+    pub fn get_status(&self) -> GameStatus {
+        self.status
+    }
+
+    /// Continue playing after the game is won. Returns false if the game is not in `Won` status.
+    /// The game is lost at once if the winning movement left no movement possible.
+    pub fn keep_going(&mut self) -> bool {
+        if self.status == GameStatus::Won {
+            self.status = GameStatus::WonContinuing;
+            self.update_status();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Check whether the game accepts a new action in the current status
+    fn accepts_action(&self) -> bool {
+        matches!(self.status, GameStatus::Playing | GameStatus::WonContinuing)
+    }
+
+    /// Update the game status by the current board
+    fn update_status(&mut self) {
+        if self.status == GameStatus::Playing && self.board.max_tile() >= WIN_TILE {
+            self.status = GameStatus::Won;
+        } else if self.accepts_action() && !self.board.can_move() {
+            self.status = GameStatus::Lost;
         }
     }
 
@@ -90,17 +504,33 @@ impl Game {
         self.steps
     }
 
-    /// Generate a new tile and increment steps. If return values is false, it means 
-    /// there is no empty tile for new tile generation.
-    pub fn next(&mut self) -> bool {
-        let r = self.board.generate_new_tile();
-        self.steps += if r {1} else {0};
-        r
+    /// Get the score of the current game, which is the sum of all merged tile values
+    pub fn get_score(&self) -> u32 {
+        self.score
+    }
+
+    /// Get the best score among all games played by this `Game`
+    pub fn get_best_score(&self) -> u32 {
+        self.best_score
+    }
+
+    /// Generate a new tile and increment steps, then update the game status
+    fn next(&mut self) {
+        if self.policy.spawn(&mut self.board, &mut self.rng) {
+            self.steps += 1;
+        }
+        self.update_status();
     }
 
-    /// Reset the game steps, and internal board (all tile values are 0)
+    /// Reset the game steps, score, history, and internal board (all tile values are 0).
+    /// The best score is kept.
     fn reset_game(&mut self) {
         self.steps = 0;
+        self.score = 0;
+        self.status = GameStatus::Playing;
+        self.undo_history.clear();
+        self.redo_history.clear();
+        self.undos_left = self.undo_limit;
         self.board.reset();
     }
 }
@@ -109,6 +539,7 @@ impl Game {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
 
     #[test]
     fn test_start_game() {
@@ -116,6 +547,16 @@ mod tests {
         game.start_game();
     }
 
+    /// Check the board equals to the expected values except one new tile (2 or 4) generated
+    /// on an empty tile
+    fn assert_moved(game: &Game, expected: &[u32]) {
+        let values = game.board.get_values();
+        let diff: Vec<usize> = (0..16).filter(|&i| values[i] != expected[i]).collect();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(expected[diff[0]], 0);
+        assert!(values[diff[0]] == 2 || values[diff[0]] == 4);
+    }
+
     #[test]
     fn test_actions() {
         let int_values = vec![
@@ -152,15 +593,393 @@ mod tests {
             ];
         let mut game = Game::new_with_values(int_values.clone());
         game.action(Direction::Left);
-        assert_eq!(game.board.get_values().to_vec(), left_expected_values);
+        assert_moved(&game, &left_expected_values);
         let mut game = Game::new_with_values(int_values.clone());
         game.action(Direction::Right);
-        assert_eq!(game.board.get_values().to_vec(), right_expected_values);
+        assert_moved(&game, &right_expected_values);
         let mut game = Game::new_with_values(int_values.clone());
         game.action(Direction::Up);
-        assert_eq!(game.board.get_values().to_vec(), up_expected_values);
+        assert_moved(&game, &up_expected_values);
+        let mut game = Game::new_with_values(int_values.clone());
+        game.action(Direction::Down);
+        assert_moved(&game, &down_expected_values);
+    }
+
+    #[test]
+    fn test_action_not_moved() {
+        let int_values = vec![
+                2,4,0,0,
+                0,0,0,0,
+                0,0,0,0,
+                0,0,0,0,
+            ];
         let mut game = Game::new_with_values(int_values.clone());
+        let info = game.action(Direction::Left);
+        assert!(!info.moved);
+        assert_eq!(game.get_steps(), 0);
+        assert_eq!(game.board.get_values().to_vec(), int_values);
+
+        let info = game.action(Direction::Right);
+        assert!(info.moved);
+        assert_eq!(game.get_steps(), 1);
+    }
+
+    #[test]
+    fn test_score() {
+        let int_values = vec![
+                2,2,4,0,
+                0,0,0,0,
+                0,0,0,0,
+                0,0,0,0,
+            ];
+        let mut game = Game::new_with_values(int_values);
+        let info = game.action(Direction::Left);
+        assert_eq!(info, MoveInfo { moved: true, merges: 1, score: 4 });
+        assert_eq!(game.get_score(), 4);
+        assert_eq!(game.get_best_score(), 4);
+
+        game.start_game();
+        assert_eq!(game.get_score(), 0);
+        assert_eq!(game.get_best_score(), 4);
+    }
+
+    #[test]
+    fn test_status_won() {
+        let int_values = vec![
+                1024,1024,0,0,
+                0,0,0,0,
+                0,0,0,0,
+                0,0,0,0,
+            ];
+        let mut game = Game::new_with_values(int_values);
+        assert_eq!(game.get_status(), GameStatus::Playing);
+        game.action(Direction::Left);
+        assert_eq!(game.get_status(), GameStatus::Won);
+        assert_eq!(game.get_steps(), 1);
+
+        // actions are ignored until keep going
+        assert!(!game.action(Direction::Right).moved);
+        assert_eq!(game.board.get_values()[0], 2048);
+        assert_eq!(game.get_steps(), 1);
+
+        assert!(game.keep_going());
+        assert_eq!(game.get_status(), GameStatus::WonContinuing);
+        assert!(!game.keep_going());
+        assert!(game.action(Direction::Right).moved);
+        assert_eq!(game.get_status(), GameStatus::WonContinuing);
+    }
+
+    #[test]
+    fn test_status_won_lost() {
+        // the winning movement fills the board without any possible merge
+        let int_values = vec![
+                1024,1024,4,8,
+                2,8,16,32,
+                4,16,32,64,
+                2,4,8,16,
+            ];
+        let mut game = Game::new_with_values(int_values);
+        game.action(Direction::Left);
+        assert_eq!(game.get_status(), GameStatus::Won);
+        assert!(!game.get_board().can_move());
+        assert!(game.keep_going());
+        assert_eq!(game.get_status(), GameStatus::Lost);
+    }
+
+    #[test]
+    fn test_status_lost() {
+        let int_values = vec![
+                2,4,2,4,
+                4,2,4,2,
+                8,4,2,8,
+                16,8,2,0,
+            ];
+        let mut game = Game::new_with_values(int_values);
+        assert!(!game.action(Direction::Left).moved);
+        assert_eq!(game.get_status(), GameStatus::Playing);
+        // the last empty tile is filled with either 2 or 4, and no merge is possible
+        assert!(game.action(Direction::Right).moved);
+        assert_eq!(game.get_status(), GameStatus::Lost);
+        assert!(!game.action(Direction::Down).moved);
+
+        game.start_game();
+        assert_eq!(game.get_status(), GameStatus::Playing);
+    }
+
+    #[test]
+    fn test_policy() {
+        use crate::spawn::{Difficulty, OnlyTwosSpawn, WeightedSpawn};
+
+        let mut game = Game::with_policy(OnlyTwosSpawn);
+        game.start_game();
+        assert!(game.get_board().get_values().iter().all(|val| *val == 0 || *val == 2));
+
+        let mut game = Game::with_policy(Difficulty::Easy.policy());
+        game.start_game_with_seed(1);
+        game.set_policy(Box::new(WeightedSpawn::new(1.0)));
+        game.start_game();
+        assert!(game.get_board().get_values().iter().all(|val| *val == 0 || *val == 4));
+    }
+
+    #[test]
+    fn test_seed() {
+        fn play(game: &mut Game) {
+            for _ in 0..10 {
+                game.action(Direction::Left);
+                game.action(Direction::Up);
+                game.action(Direction::Right);
+                game.action(Direction::Down);
+            }
+        }
+
+        let mut game1 = Game::with_seed(42);
+        let mut game2 = Game::new();
+        game1.start_game();
+        game2.start_game_with_seed(42);
+        assert_eq!(game1.get_seed(), 42);
+        assert_eq!(game2.get_seed(), 42);
+        play(&mut game1);
+        play(&mut game2);
+        assert_eq!(game1.get_board().get_values(), game2.get_board().get_values());
+        assert_eq!(game1.get_score(), game2.get_score());
+        assert_eq!(game1.get_steps(), game2.get_steps());
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+        let game3 = Game::from_rng(&mut rng);
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+        assert_eq!(game3.get_seed(), rng.next_u64());
+
+        // the following game is seeded by the OS
+        game1.start_game();
+        let seed = game1.get_seed();
+        let values = game1.get_board().get_values();
+        game2.start_game_with_seed(seed);
+        assert_eq!(game2.get_board().get_values(), values);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut game = Game::with_seed(3);
+        game.start_game();
+        assert!(!game.can_undo());
+        assert!(!game.undo());
+
+        let start_values = game.get_board().get_values();
+        let mut moved = Vec::new();
+        for dir in Direction::ALL {
+            if game.action(dir).moved {
+                moved.push(game.get_board().get_values());
+            }
+        }
+        let score = game.get_score();
+        let steps = game.get_steps();
+
+        // undo all actions
+        for values in moved.iter().rev() {
+            assert_eq!(&game.get_board().get_values(), values);
+            assert!(game.undo());
+        }
+        assert_eq!(game.get_board().get_values(), start_values);
+        assert_eq!(game.get_steps(), 0);
+        assert_eq!(game.get_score(), 0);
+        assert!(!game.undo());
+
+        // redo generates the same tiles
+        for values in moved.iter() {
+            assert!(game.redo());
+            assert_eq!(&game.get_board().get_values(), values);
+        }
+        assert!(!game.redo());
+        assert_eq!(game.get_score(), score);
+        assert_eq!(game.get_steps(), steps);
+
+        // a new action clears the redo history
+        assert!(game.undo());
+        assert!(game.can_redo());
+        game.action(Direction::Left);
+        game.action(Direction::Right);
+        assert!(!game.can_redo());
+    }
+
+    #[test]
+    fn test_undo_same_tile() {
+        let int_values = vec![
+                2,0,0,0,
+                0,0,0,0,
+                0,0,0,0,
+                0,0,0,0,
+            ];
+        let mut game = Game::new_with_values(int_values);
+        game.action(Direction::Right);
+        let values = game.get_board().get_values();
+        assert!(game.undo());
+        game.action(Direction::Right);
+        assert_eq!(game.get_board().get_values(), values);
+    }
+
+    #[test]
+    fn test_undo_limit() {
+        let mut game = Game::with_seed(5);
+        game.set_undo_limit(Some(1));
+        game.start_game();
+        game.action(Direction::Left);
+        game.action(Direction::Right);
+        game.action(Direction::Left);
+        assert_eq!(game.get_undos_left(), Some(1));
+        assert!(game.undo());
+        assert_eq!(game.get_undos_left(), Some(0));
+        assert!(!game.can_undo());
+        assert!(!game.undo());
+        // redo is still possible
+        assert!(game.redo());
+
+        game.start_game();
+        assert_eq!(game.get_undos_left(), Some(1));
+        game.set_undo_limit(None);
+        assert_eq!(game.get_undos_left(), None);
+    }
+
+    #[test]
+    fn test_history_size() {
+        let int_values = vec![
+                2,0,0,0,
+                0,0,0,0,
+                0,0,0,0,
+                0,0,0,0,
+            ];
+        let mut game = Game::new_with_values(int_values);
+        game.set_history_size(2);
+        game.action(Direction::Right);
         game.action(Direction::Down);
-        assert_eq!(game.board.get_values().to_vec(), down_expected_values);
+        game.action(Direction::Left);
+        assert!(game.undo());
+        assert!(game.undo());
+        assert!(!game.undo());
+        assert_eq!(game.get_steps(), 1);
+
+        game.set_history_size(0);
+        game.action(Direction::Up);
+        assert!(!game.can_undo());
+
+        // shrinking keeps the redo snapshots nearest to the current state, and can be saved
+        let mut game = Game::with_seed(3);
+        game.start_game();
+        for dir in Direction::ALL.iter().cycle().take(20) {
+            game.action(*dir);
+        }
+        for _ in 0..5 {
+            assert!(game.undo());
+        }
+        let steps = game.get_steps();
+        game.set_history_size(2);
+        let mut loaded = Game::load(&game.save(), ClassicSpawn).unwrap();
+        assert!(loaded.redo());
+        assert!(loaded.redo());
+        assert!(!loaded.redo());
+        assert_eq!(loaded.get_steps(), steps + 2);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut game = Game::with_seed(11);
+        game.set_undo_limit(Some(3));
+        game.start_game();
+        for _ in 0..5 {
+            game.action(Direction::Left);
+            game.action(Direction::Down);
+        }
+        game.undo();
+
+        let text = game.save();
+        assert!(text.starts_with("wasm_terminal_2048 save 1\n"));
+        let mut loaded = Game::load(&text, ClassicSpawn).unwrap();
+        assert_eq!(loaded.save(), text);
+        assert_eq!(loaded.get_seed(), 11);
+        assert_eq!(loaded.get_undos_left(), Some(2));
+        assert!(loaded.can_redo());
+
+        // the loaded game continues exactly as the saved one
+        for game in [&mut game, &mut loaded] {
+            game.redo();
+            game.undo();
+            game.action(Direction::Up);
+            game.action(Direction::Right);
+        }
+        assert_eq!(loaded.get_board(), game.get_board());
+        assert_eq!(loaded.get_score(), game.get_score());
+        assert_eq!(loaded.get_steps(), game.get_steps());
+        assert_eq!(loaded.get_best_score(), game.get_best_score());
+        assert_eq!(loaded.get_status(), game.get_status());
+
+        let err = Game::load(&text.replace("history_size", "history"), ClassicSpawn).err();
+        assert!(matches!(err, Some(LoadError::InvalidLine { line: 6, .. })));
+        let err = Game::load(&text.replace("history_size 100", "history_size 2"), ClassicSpawn).err();
+        assert!(matches!(err, Some(LoadError::Inconsistent(_))));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut game = Game::with_seed(13);
+        game.start_game();
+        game.action(Direction::Left);
+        game.action(Direction::Up);
+        game.undo();
+
+        let json = serde_json::to_string(&game).unwrap();
+        let loaded: Game = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.save(), game.save());
+
+        // the history exceeds the history size
+        let mut value = serde_json::to_value(&game).unwrap();
+        value["history_size"] = serde_json::json!(0);
+        assert!(serde_json::from_value::<Game>(value).is_err());
+
+        let json = serde_json::to_string(&(game.get_board(), Direction::Down)).unwrap();
+        let (board, dir): (Board, Direction) = serde_json::from_str(&json).unwrap();
+        assert_eq!(&board, game.get_board());
+        assert_eq!(dir, Direction::Down);
+        // boards are checked as `Board::from_values`
+        let err = serde_json::from_str::<Board>(r#"{"exponents":[1,2,3],"rows":4,"cols":4}"#).unwrap_err();
+        assert!(err.to_string().contains("expected 16 tiles, found 3"), "{}", err);
+        let err = serde_json::from_str::<Board>(r#"{"exponents":[1,2,32,0],"rows":2,"cols":2}"#).unwrap_err();
+        assert!(err.to_string().contains("invalid tile `2^32`"), "{}", err);
+        assert!(serde_json::from_str::<Board>(r#"{"exponents":[1,2,31,0],"rows":2,"cols":2}"#).is_ok());
+    }
+
+    #[test]
+    fn test_send() {
+        use crate::spawn::Difficulty;
+
+        fn assert_send<T: Send>(_: &T) {}
+        let mut game = Game::with_policy(Difficulty::Expert.policy());
+        assert_send(&game);
+        game.start_game_with_seed(9);
+        let values = std::thread::spawn(move || {
+            game.action(Direction::Left);
+            game.get_board().get_values()
+        }).join().unwrap();
+        assert_eq!(values.len(), 16);
+    }
+
+    #[test]
+    fn test_size() {
+        let mut game = Game::with_size(3, 5);
+        game.start_game();
+        assert_eq!(game.get_board().get_rows(), 3);
+        assert_eq!(game.get_board().get_cols(), 5);
+        assert_eq!(game.get_board().get_values().len(), 15);
+        assert_eq!(game.get_board().empty_tiles().len(), 14);
+
+        for _ in 0..10 {
+            game.action(Direction::Left);
+            game.action(Direction::Down);
+        }
+        assert_eq!(game.get_board().get_values().len(), 15);
+
+        game.set_size(6, 6);
+        game.start_game_with_seed(1);
+        assert_eq!(game.get_board().get_values().len(), 36);
+        assert_eq!(game.get_board().empty_tiles().len(), 35);
     }
 }
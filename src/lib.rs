@@ -1,2 +1,12 @@
 pub mod tile;
 pub mod game;
+pub mod spawn;
+pub mod bitboard;
+pub mod save;
+pub mod symmetry;
+pub mod evaluator;
+pub mod solver;
+pub mod rollout;
+pub mod agent;
+pub mod ntuple;
+pub mod args;
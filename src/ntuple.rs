@@ -0,0 +1,516 @@
+use crate::agent::{pick, Agent};
+use crate::evaluator::Evaluator;
+use crate::game::Game;
+use crate::spawn::SpawnPolicy;
+use crate::symmetry::Transform;
+use crate::tile::{Board, Direction};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// The header of weight files
+pub const WEIGHTS_HEADER: &str = "wasm_terminal_2048 ntuple";
+
+/// The version of weight files
+pub const WEIGHTS_VERSION: u32 = 1;
+
+/// The number of values of a cell in a feature. Tiles of 32768 and above share the last value.
+const CELL_VALUES: usize = 16;
+
+/// The largest number of cells of a tuple, which has `CELL_VALUES ^ 6` weights
+pub const MAX_TUPLE_LENGTH: usize = 6;
+
+/// The 4 tuples of 6 cells on a 4x4 board, which are two rectangles and two lines of 2 rows.
+/// With the symmetries they are the usual strong network, which has 64M weights.
+pub const STANDARD_TUPLES: [[usize; 6]; 4] = [
+    [0, 1, 2, 3, 4, 5],
+    [4, 5, 6, 7, 8, 9],
+    [0, 1, 2, 4, 5, 6],
+    [4, 5, 6, 8, 9, 10],
+];
+
+/// The 5 tuples of 4 cells on a 4x4 board, which are the outer and inner rows and three
+/// squares. With the symmetries they cover all lines and squares, and the network is small
+/// enough (1.3M weights) to be trained quickly.
+pub const SMALL_TUPLES: [[usize; 4]; 5] = [
+    [0, 1, 2, 3],
+    [4, 5, 6, 7],
+    [0, 1, 4, 5],
+    [1, 2, 5, 6],
+    [5, 6, 9, 10],
+];
+
+/// The default learning rate of `TdTrainer`, which is divided by the number of features
+pub const DEFAULT_LEARNING_RATE: f32 = 0.1;
+
+/// Errors of n-tuple networks
+///
+/// * `InvalidSize` The board size is not supported, with the size
+/// * `InvalidTuple` A tuple is empty, too long or has a cell out of the board, with the tuple
+/// * `Io` The file can not be read or written, with the reason
+/// * `InvalidFormat` The weight file is malformed, with the reason
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NTupleError {
+    InvalidSize(usize),
+    InvalidTuple(Vec<usize>),
+    Io(String),
+    InvalidFormat(String),
+}
+
+impl Display for NTupleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            NTupleError::InvalidSize(size) => write!(f, "invalid board size: {}", size),
+            NTupleError::InvalidTuple(tuple) => write!(f, "invalid tuple: {:?}", tuple),
+            NTupleError::Io(reason) => write!(f, "{}", reason),
+            NTupleError::InvalidFormat(reason) => write!(f, "invalid weight file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for NTupleError {}
+
+/// Get the cell of the board that is moved to the given cell of the transformed board
+///
+/// # Arguments
+/// * `transform` The transform of the board
+/// * `size` The number of rows and columns of the square board
+/// * `cell` The cell of the transformed board
+///
+fn source_cell(transform: Transform, size: usize, cell: usize) -> usize {
+    let (row, col) = (cell / size, cell % size);
+    let last = size - 1;
+    let (row, col) = match transform {
+        Transform::Identity => (row, col),
+        Transform::RotateCw => (last - col, row),
+        Transform::Rotate180 => (last - row, last - col),
+        Transform::RotateCcw => (col, last - row),
+        Transform::ReflectHorizontal => (row, last - col),
+        Transform::ReflectVertical => (last - row, col),
+        Transform::Transpose => (col, row),
+        Transform::AntiTranspose => (last - col, last - row),
+    };
+    row * size + col
+}
+
+/// `NTupleNetwork` values a square board by the sum of weights of its features. Each tuple is a
+/// list of cells, and the exponents of the tiles on the cells of a board index the weight of
+/// the tuple. The tuples are applied on all the 8 symmetries of a board (see `Transform`), so
+/// the weights are shared by symmetric boards.
+///
+/// The weights are learned by `TdTrainer` as the expected score of the rest of a game after a
+/// movement, before a new tile is generated. The network is an `Evaluator`, and an `Agent` that
+/// chooses the movement of the largest score plus the value of the moved board.
+///
+/// ```ignore
+///  let mut network = NTupleNetwork::new(4, &SMALL_TUPLES)?;
+///  TdTrainer::with_seed(0).train(&mut network, 10000);
+///  network.save("weights.ntuple")?;
+/// ```
+#[derive(Clone)]
+pub struct NTupleNetwork {
+    size: usize,
+    tuples: Vec<Vec<usize>>,
+    // the cells of each tuple on each symmetry, with the index of the tuple
+    features: Vec<(usize, Vec<usize>)>,
+    weights: Vec<Vec<f32>>,
+}
+
+impl NTupleNetwork {
+    /// Create a new `NTupleNetwork` with all weights 0
+    ///
+    /// # Arguments
+    /// * `size` The number of rows and columns of boards
+    /// * `tuples` The cells of each tuple, a cell is indexed row by row from 0
+    ///
+    pub fn new<T: AsRef<[usize]>>(size: usize, tuples: &[T]) -> Result<Self, NTupleError> {
+        let cells = match size.checked_mul(size) {
+            Some(cells) if size >= 2 => cells,
+            _ => return Err(NTupleError::InvalidSize(size)),
+        };
+        let tuples: Vec<Vec<usize>> = tuples.iter().map(|tuple| tuple.as_ref().to_vec()).collect();
+        if let Some(tuple) = tuples.iter().find(|tuple| {
+            tuple.is_empty() || tuple.len() > MAX_TUPLE_LENGTH || tuple.iter().any(|cell| *cell >= cells)
+        }) {
+            return Err(NTupleError::InvalidTuple(tuple.clone()));
+        }
+        let features = tuples.iter().enumerate()
+            .flat_map(|(idx, tuple)| Transform::ALL.iter().map(move |transform| {
+                (idx, tuple.iter().map(|cell| source_cell(*transform, size, *cell)).collect())
+            }))
+            .collect();
+        let weights = tuples.iter().map(|tuple| vec![0.0; CELL_VALUES.pow(tuple.len() as u32)]).collect();
+        Ok(NTupleNetwork { size, tuples, features, weights })
+    }
+
+    /// Get the number of rows and columns of boards
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    /// Get the cells of each tuple
+    pub fn get_tuples(&self) -> &[Vec<usize>] {
+        &self.tuples
+    }
+
+    /// Get the indexes of the weights of all features of the board, with the index of the tuple
+    fn feature_indexes<'a>(&'a self, board: &'a Board) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let exponents = board.get_exponents();
+        self.features.iter().map(move |(tuple, cells)| {
+            let index = cells.iter().fold(0, |index, cell| {
+                index * CELL_VALUES + (exponents[*cell] as usize).min(CELL_VALUES - 1)
+            });
+            (*tuple, index)
+        })
+    }
+
+    /// Check the board can be valued by the network
+    fn fits(&self, board: &Board) -> bool {
+        board.get_rows() == self.size && board.get_cols() == self.size
+    }
+
+    /// Add the given amount to the value of the board, which is shared by all features
+    ///
+    /// # Arguments
+    /// * `board` The board to update
+    /// * `delta` The amount to add to the value
+    ///
+    fn update(&mut self, board: &Board, delta: f32) {
+        let step = delta / self.features.len() as f32;
+        let indexes: Vec<(usize, usize)> = self.feature_indexes(board).collect();
+        for (tuple, index) in indexes {
+            self.weights[tuple][index] += step;
+        }
+    }
+
+    /// Find the movement of the largest score plus the value of the moved board. Returns the
+    /// direction, the moved board and the score, or `None` if no movement is possible.
+    fn best_afterstate(&self, board: &Board) -> Option<(Direction, Board, u32)> {
+        let moves: Vec<(Direction, Board, u32)> = Direction::ALL.iter()
+            .filter_map(|dir| board.apply(*dir).map(|(next, info)| (*dir, next, info.score)))
+            .collect();
+        let dir = pick(moves.iter().map(|(dir, next, score)| (*dir, f64::from(*score) + self.evaluate(next))))?;
+        moves.into_iter().find(|(other, _, _)| *other == dir)
+    }
+
+    /// Get the weight file of the network. The file starts with text lines of the header, the
+    /// size and the tuples, followed by the weights of all tuples as little-endian `f32`.
+    ///
+    /// ```text
+    /// wasm_terminal_2048 ntuple 1
+    /// size 4
+    /// tuple 0 1 2 3
+    /// tuple 4 5 6 7
+    /// weights
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut text = format!("{} {}\nsize {}\n", WEIGHTS_HEADER, WEIGHTS_VERSION, self.size);
+        for tuple in self.tuples.iter() {
+            let cells: Vec<String> = tuple.iter().map(|cell| cell.to_string()).collect();
+            text.push_str(&format!("tuple {}\n", cells.join(" ")));
+        }
+        text.push_str("weights\n");
+        let mut bytes = text.into_bytes();
+        for weight in self.weights.iter().flatten() {
+            bytes.extend_from_slice(&weight.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Create a new `NTupleNetwork` from the weight file, see `to_bytes`
+    ///
+    /// # Arguments
+    /// * `bytes` The content of the weight file
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, NTupleError> {
+        let invalid = |reason: &str| NTupleError::InvalidFormat(reason.to_string());
+        let mut rest = bytes;
+        let mut next_line = || -> Result<String, NTupleError> {
+            let end = rest.iter().position(|byte| *byte == b'\n').ok_or_else(|| invalid("missing weights"))?;
+            let line = std::str::from_utf8(&rest[..end]).map_err(|_| invalid("invalid text"))?.to_string();
+            rest = &rest[end + 1..];
+            Ok(line)
+        };
+
+        let header = next_line()?;
+        let version = header.strip_prefix(WEIGHTS_HEADER).ok_or_else(|| invalid("invalid header"))?;
+        match version.trim().parse::<u32>() {
+            Ok(WEIGHTS_VERSION) => {},
+            Ok(version) => return Err(NTupleError::InvalidFormat(format!("unsupported version {}", version))),
+            Err(_) => return Err(invalid("invalid header")),
+        }
+        let size = next_line()?.strip_prefix("size ").and_then(|size| size.trim().parse().ok())
+            .ok_or_else(|| invalid("invalid size"))?;
+        let mut tuples = Vec::new();
+        loop {
+            let line = next_line()?;
+            if line == "weights" {
+                break;
+            }
+            let tuple: Vec<usize> = line.strip_prefix("tuple ")
+                .and_then(|cells| cells.split_whitespace().map(|cell| cell.parse().ok()).collect())
+                .ok_or_else(|| NTupleError::InvalidFormat(format!("invalid tuple: {}", line)))?;
+            tuples.push(tuple);
+        }
+
+        // the weights are counted before they are allocated, so a short file can not ask for more
+        let bytes = tuples.iter()
+            .try_fold(0usize, |count, tuple| count.checked_add(CELL_VALUES.checked_pow(tuple.len() as u32)?))
+            .and_then(|count| count.checked_mul(4));
+        if bytes != Some(rest.len()) {
+            return Err(invalid("wrong number of weights"));
+        }
+        let mut network = Self::new(size, &tuples)?;
+        let mut values = rest.chunks_exact(4).map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        for weight in network.weights.iter_mut().flatten() {
+            *weight = values.next().unwrap_or_default();
+        }
+        Ok(network)
+    }
+
+    /// Write the weight file, see `to_bytes`
+    ///
+    /// # Arguments
+    /// * `path` The path of the weight file
+    ///
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), NTupleError> {
+        std::fs::write(path, self.to_bytes()).map_err(|err| NTupleError::Io(err.to_string()))
+    }
+
+    /// Read the weight file, see `to_bytes`
+    ///
+    /// # Arguments
+    /// * `path` The path of the weight file
+    ///
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, NTupleError> {
+        let bytes = std::fs::read(path).map_err(|err| NTupleError::Io(err.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+impl Evaluator for NTupleNetwork {
+    /// Boards of other sizes are valued 0
+    fn evaluate(&self, board: &Board) -> f64 {
+        if !self.fits(board) {
+            return 0.0;
+        }
+        self.feature_indexes(board).map(|(tuple, index)| f64::from(self.weights[tuple][index])).sum()
+    }
+}
+
+impl<P: SpawnPolicy> Agent<P> for NTupleNetwork {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        let mut network: &NTupleNetwork = self;
+        network.choose(game)
+    }
+}
+
+// the network is not changed by playing, so it can be shared by many games
+impl<P: SpawnPolicy> Agent<P> for &NTupleNetwork {
+    fn choose(&mut self, game: &Game<P>) -> Option<Direction> {
+        self.best_afterstate(game.get_board()).map(|(dir, _, _)| dir)
+    }
+}
+
+/// `TdTrainer` learns the weights of an `NTupleNetwork` by self-play with the temporal
+/// difference learning of afterstates, which are the boards after movements and before new
+/// tiles. The network plays each game by its own values, and new tiles are generated by the
+/// classic rule (see `ClassicSpawn`).
+///
+/// The weights are updated at the end of each game from the last afterstate to the first one
+/// toward the λ-return, so the `lambda` 0 is TD(0). The random generator is seeded, so the
+/// same seed always learns the same weights.
+pub struct TdTrainer {
+    learning_rate: f32,
+    lambda: f32,
+    rng: ChaCha8Rng,
+}
+
+impl Default for TdTrainer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TdTrainer {
+    /// Create a new TD(0) `TdTrainer` with the `DEFAULT_LEARNING_RATE`. The random generator
+    /// is seeded by the OS.
+    pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    /// Create a new TD(0) `TdTrainer` with the `DEFAULT_LEARNING_RATE`. The random generator
+    /// is seeded by the given seed.
+    ///
+    /// # Arguments
+    /// * `seed` The seed of the random generator
+    ///
+    pub fn with_seed(seed: u64) -> Self {
+        TdTrainer {
+            learning_rate: DEFAULT_LEARNING_RATE,
+            lambda: 0.0,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Set the learning rate, which is divided by the number of features of the network
+    ///
+    /// # Arguments
+    /// * `learning_rate` The learning rate
+    ///
+    pub fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    /// Set the λ of TD(λ) between 0 and 1
+    ///
+    /// # Arguments
+    /// * `lambda` The weight of the later rewards
+    ///
+    pub fn set_lambda(&mut self, lambda: f32) {
+        self.lambda = lambda.clamp(0.0, 1.0);
+    }
+
+    /// Play a game by the network and learn from it. Returns the score of the game.
+    ///
+    /// # Arguments
+    /// * `network` The network to train
+    ///
+    pub fn train_game(&mut self, network: &mut NTupleNetwork) -> u32 {
+        let size = network.get_size();
+        let mut board = Board::with_size(size, size, vec![0; size * size]);
+        board.generate_new_tile(&mut self.rng);
+        board.generate_new_tile(&mut self.rng);
+
+        let mut afterstates = Vec::new();
+        let mut rewards = Vec::new();
+        while let Some((_, next, reward)) = network.best_afterstate(&board) {
+            afterstates.push(next.clone());
+            rewards.push(reward);
+            board = next;
+            board.generate_new_tile(&mut self.rng);
+        }
+
+        // the last afterstate leads to the end of the game, so its return is 0
+        let mut target = 0.0;
+        for (afterstate, reward) in afterstates.iter().zip(rewards.iter()).rev() {
+            let value = network.evaluate(afterstate) as f32;
+            network.update(afterstate, self.learning_rate * (target - value));
+            let value = network.evaluate(afterstate) as f32;
+            target = *reward as f32 + (1.0 - self.lambda) * value + self.lambda * target;
+        }
+        rewards.iter().sum()
+    }
+
+    /// Play and learn the given number of games. Returns the score of each game.
+    ///
+    /// # Arguments
+    /// * `network` The network to train
+    /// * `games` The number of games
+    ///
+    pub fn train(&mut self, network: &mut NTupleNetwork, games: u32) -> Vec<u32> {
+        (0..games).map(|_| self.train_game(network)).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_cell() {
+        let board: Board = "2,4,8,16/32,64,128,256/512,1024,2048,4096/8192,16384,32768,0".parse().unwrap();
+        for transform in Transform::ALL.iter() {
+            let transformed = transform.apply(&board);
+            for cell in 0..16 {
+                let source = source_cell(*transform, 4, cell);
+                assert_eq!(transformed.get_exponents()[cell], board.get_exponents()[source], "{:?}", transform);
+            }
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        assert_eq!(NTupleNetwork::new(1, &SMALL_TUPLES).err(), Some(NTupleError::InvalidSize(1)));
+        assert_eq!(NTupleNetwork::new(3, &SMALL_TUPLES).err(), Some(NTupleError::InvalidTuple(vec![5, 6, 9, 10])));
+        let empty: [&[usize]; 1] = [&[]];
+        assert!(NTupleNetwork::new(4, &empty).is_err());
+        assert!(NTupleNetwork::new(4, &[[0, 1, 2, 3, 4, 5, 6]]).is_err());
+        assert_eq!(NTupleNetwork::new(1 << 32, &SMALL_TUPLES).err(), Some(NTupleError::InvalidSize(1 << 32)));
+
+        let network = NTupleNetwork::new(4, &SMALL_TUPLES).unwrap();
+        assert_eq!(network.get_tuples().len(), 5);
+        assert_eq!(network.evaluate(&Board::default()), 0.0);
+    }
+
+    #[test]
+    fn test_symmetric_value() {
+        let mut network = NTupleNetwork::new(4, &SMALL_TUPLES).unwrap();
+        let board: Board = "2,4,0,0/0,8,0,0/0,0,0,0/0,0,0,2".parse().unwrap();
+        network.update(&board, 4.0);
+        let value = network.evaluate(&board);
+        assert!(value >= 4.0 - 1e-5);
+        for transform in Transform::ALL.iter() {
+            assert!((network.evaluate(&transform.apply(&board)) - value).abs() < 1e-5);
+        }
+        assert_eq!(network.evaluate(&"2,4,8/0,0,0/0,0,0".parse().unwrap()), 0.0);
+    }
+
+    #[test]
+    fn test_train() {
+        let mut network1 = NTupleNetwork::new(4, &SMALL_TUPLES).unwrap();
+        let mut network2 = network1.clone();
+        let scores = TdTrainer::with_seed(1).train(&mut network1, 100);
+        assert_eq!(TdTrainer::with_seed(1).train(&mut network2, 100), scores);
+        assert_eq!(network1.to_bytes(), network2.to_bytes());
+        // the network learns to play better than its initial random play
+        let average = |scores: &[u32]| scores.iter().sum::<u32>() / scores.len() as u32;
+        assert!(average(&scores[75..]) > average(&scores[..25]), "{:?}", scores);
+
+        let mut trainer = TdTrainer::with_seed(2);
+        trainer.set_lambda(0.5);
+        trainer.set_learning_rate(0.05);
+        assert_eq!(trainer.train(&mut network1, 2).len(), 2);
+
+        let mut game = Game::with_seed(3);
+        game.start_game();
+        let dir = network1.choose(&game).unwrap();
+        assert!(game.get_board().legal_moves().contains(&dir));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut network = NTupleNetwork::new(4, &[[0, 1, 2], [4, 5, 6]]).unwrap();
+        TdTrainer::with_seed(0).train(&mut network, 5);
+        let bytes = network.to_bytes();
+        assert!(bytes.starts_with(b"wasm_terminal_2048 ntuple 1\nsize 4\ntuple 0 1 2\ntuple 4 5 6\nweights\n"));
+        let loaded = NTupleNetwork::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.to_bytes(), bytes);
+
+        let path = std::env::temp_dir().join("wasm_terminal_2048_test.ntuple");
+        network.save(&path).unwrap();
+        assert_eq!(NTupleNetwork::load(&path).unwrap().to_bytes(), bytes);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(NTupleNetwork::load(&path), Err(NTupleError::Io(_))));
+
+        assert!(matches!(NTupleNetwork::from_bytes(&bytes[..bytes.len() - 1]), Err(NTupleError::InvalidFormat(_))));
+        assert!(matches!(NTupleNetwork::from_bytes(b"other 1\n"), Err(NTupleError::InvalidFormat(_))));
+        assert!(matches!(NTupleNetwork::from_bytes(b"wasm_terminal_2048 ntuple 2\n"), Err(NTupleError::InvalidFormat(_))));
+        let mut invalid = b"wasm_terminal_2048 ntuple 1\nsize 2\ntuple 0 4\nweights\n".to_vec();
+        invalid.extend_from_slice(&[0; 16 * 16 * 4]);
+        assert_eq!(NTupleNetwork::from_bytes(&invalid).err(), Some(NTupleError::InvalidTuple(vec![0, 4])));
+        let mut invalid = b"wasm_terminal_2048 ntuple 1\nsize 4294967296\ntuple 0 1\nweights\n".to_vec();
+        invalid.extend_from_slice(&[0; 16 * 16 * 4]);
+        assert_eq!(NTupleNetwork::from_bytes(&invalid).err(), Some(NTupleError::InvalidSize(1 << 32)));
+        // many large tuples without the weights
+        let mut invalid = b"wasm_terminal_2048 ntuple 1\nsize 4\n".to_vec();
+        for _ in 0..1000 {
+            invalid.extend_from_slice(b"tuple 0 1 2 3 4 5\n");
+        }
+        invalid.extend_from_slice(b"weights\n");
+        assert_eq!(NTupleNetwork::from_bytes(&invalid).err(),
+            Some(NTupleError::InvalidFormat("wrong number of weights".to_string())));
+    }
+}
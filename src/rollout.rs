@@ -0,0 +1,253 @@
+use crate::agent::pick;
+use crate::tile::{Board, Direction};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// The default number of playouts per movement
+pub const DEFAULT_ROLLOUTS: u32 = 100;
+
+/// The default number of movements per playout
+pub const DEFAULT_ROLLOUT_DEPTH: u32 = 20;
+
+/// How movements are chosen in playouts
+///
+/// * `Random` A uniformly chosen movement
+/// * `Greedy` The movement of the largest score, and then the most empty tiles. Ties are
+///   broken randomly
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Playout {
+    #[default]
+    Random,
+    Greedy,
+}
+
+impl Playout {
+    /// Choose a movement of the board, returns the moved board and the score of the movement,
+    /// or `None` if no movement is possible
+    fn choose(self, board: &Board, rng: &mut impl Rng) -> Option<(Board, u32)> {
+        let mut candidates: Vec<(Board, u32)> = Direction::ALL.iter()
+            .filter_map(|dir| board.apply(*dir).map(|(next, info)| (next, info.score)))
+            .collect();
+        if self == Playout::Greedy {
+            let key = |(next, score): &(Board, u32)| (*score, next.empty_tiles().len());
+            let best = candidates.iter().map(key).max()?;
+            candidates.retain(|candidate| key(candidate) == best);
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+        let idx = rng.gen_range(0..candidates.len());
+        Some(candidates.swap_remove(idx))
+    }
+}
+
+/// `RolloutAgent` finds the best movement of a board by playouts. For each movement, many
+/// games are played from the moved board by the `Playout` for a limited number of movements,
+/// and the movement of the best average score is chosen. New tiles are generated by the
+/// classic rule (see `ClassicSpawn`).
+///
+/// The random generator is seeded, so the same seed always chooses the same movements, even
+/// with threads since each thread is seeded by the agent in order.
+///
+/// ```ignore
+///  let mut agent = RolloutAgent::with_seed(100, 20, 42);
+///  agent.set_threads(4);
+///  if let Some(dir) = agent.best_move(game.get_board()) {
+///    game.action(dir);
+///  }
+/// ```
+pub struct RolloutAgent {
+    rollouts: u32,
+    depth: u32,
+    threads: usize,
+    playout: Playout,
+    rng: ChaCha8Rng,
+}
+
+impl Default for RolloutAgent {
+    fn default() -> Self {
+        Self::new(DEFAULT_ROLLOUTS, DEFAULT_ROLLOUT_DEPTH)
+    }
+}
+
+/// Play a game from the board by the playout for the given number of movements, and return
+/// the score
+fn play_out(board: &Board, depth: u32, playout: Playout, rng: &mut ChaCha8Rng) -> u32 {
+    let mut board = board.clone();
+    let mut score = 0;
+    board.generate_new_tile(rng);
+    for _ in 0..depth {
+        match playout.choose(&board, rng) {
+            Some((next, gained)) => {
+                board = next;
+                score += gained;
+                board.generate_new_tile(rng);
+            },
+            None => break,
+        }
+    }
+    score
+}
+
+/// Play the given number of games, and return the sum of the scores
+fn play_outs(board: &Board, rollouts: u32, depth: u32, playout: Playout, seed: u64) -> u64 {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    (0..rollouts).map(|_| u64::from(play_out(board, depth, playout, &mut rng))).sum()
+}
+
+impl RolloutAgent {
+    /// Create a new `RolloutAgent` with random playouts in a single thread. The random
+    /// generator is seeded by the OS.
+    ///
+    /// # Arguments
+    /// * `rollouts` The number of playouts per movement
+    /// * `depth` The number of movements per playout
+    ///
+    pub fn new(rollouts: u32, depth: u32) -> Self {
+        Self::with_seed(rollouts, depth, rand::random())
+    }
+
+    /// Create a new `RolloutAgent` with random playouts in a single thread. The random
+    /// generator is seeded by the given seed.
+    ///
+    /// # Arguments
+    /// * `rollouts` The number of playouts per movement
+    /// * `depth` The number of movements per playout
+    /// * `seed` The seed of the random generator
+    ///
+    pub fn with_seed(rollouts: u32, depth: u32, seed: u64) -> Self {
+        RolloutAgent {
+            rollouts: rollouts.max(1),
+            depth,
+            threads: 1,
+            playout: Playout::default(),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Seed the random generator by the given seed
+    ///
+    /// # Arguments
+    /// * `seed` The seed of the random generator
+    ///
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    /// Set the number of threads that play the playouts of a movement, 1 means no thread is
+    /// spawned, which is required on `wasm32-unknown-unknown`
+    ///
+    /// # Arguments
+    /// * `threads` The number of threads
+    ///
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Set how movements are chosen in playouts
+    pub fn set_playout(&mut self, playout: Playout) {
+        self.playout = playout;
+    }
+
+    /// Get the average score of the playouts of each possible movement of the given board
+    ///
+    /// # Arguments
+    /// * `board` The board to play
+    ///
+    pub fn evaluate_moves(&mut self, board: &Board) -> Vec<(Direction, f64)> {
+        let moves: Vec<(Direction, Board, u32)> = Direction::ALL.iter()
+            .filter_map(|dir| board.apply(*dir).map(|(next, info)| (*dir, next, info.score)))
+            .collect();
+        moves.iter()
+            .map(|(dir, next, score)| {
+                let total = self.play_outs(next);
+                (*dir, f64::from(*score) + total as f64 / f64::from(self.rollouts))
+            })
+            .collect()
+    }
+
+    /// Find the best movement of the given board. Returns `None` if no movement is possible.
+    ///
+    /// # Arguments
+    /// * `board` The board to play
+    ///
+    pub fn best_move(&mut self, board: &Board) -> Option<Direction> {
+        pick(self.evaluate_moves(board).into_iter())
+    }
+
+    /// Play all the playouts of a moved board, and return the sum of the scores
+    fn play_outs(&mut self, board: &Board) -> u64 {
+        let (rollouts, depth, playout) = (self.rollouts, self.depth, self.playout);
+        if self.threads == 1 {
+            return play_outs(board, rollouts, depth, playout, self.rng.next_u64());
+        }
+        // the playouts are split evenly, and each thread is seeded in order
+        let threads = self.threads.min(rollouts as usize) as u32;
+        let jobs: Vec<(u32, u64)> = (0..threads)
+            .map(|idx| (rollouts / threads + u32::from(idx < rollouts % threads), self.rng.next_u64()))
+            .collect();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = jobs.iter()
+                .map(|(count, seed)| scope.spawn(move || play_outs(board, *count, depth, playout, *seed)))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).sum()
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Game, GameStatus};
+
+    #[test]
+    fn test_best_move() {
+        let board: Board = "2,4/4,2".parse().unwrap();
+        assert_eq!(RolloutAgent::with_seed(10, 5, 0).best_move(&board), None);
+
+        // left and right merge the two 1024 tiles
+        let board: Board = "1024,1024,0,0/2,4,0,0/0,0,0,0/0,0,0,0".parse().unwrap();
+        let dir = RolloutAgent::with_seed(10, 5, 0).best_move(&board).unwrap();
+        assert!(dir == Direction::Left || dir == Direction::Right);
+    }
+
+    #[test]
+    fn test_seed() {
+        let board: Board = "2,0,0,2/0,4,0,0/0,0,8,0/2,0,0,0".parse().unwrap();
+        for threads in [1, 3] {
+            let mut agent1 = RolloutAgent::with_seed(20, 10, 7);
+            let mut agent2 = RolloutAgent::with_seed(20, 10, 0);
+            agent2.reseed(7);
+            agent1.set_threads(threads);
+            agent2.set_threads(threads);
+            assert_eq!(agent1.evaluate_moves(&board), agent2.evaluate_moves(&board));
+        }
+    }
+
+    #[test]
+    fn test_playout() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        // the greedy playout always merges
+        let board: Board = "2,2,0/0,0,0/0,0,4".parse().unwrap();
+        let (next, score) = Playout::Greedy.choose(&board, &mut rng).unwrap();
+        assert_eq!(score, 4);
+        assert!(next.get_values().contains(&4));
+        assert!(Playout::Random.choose(&"2,4/4,2".parse().unwrap(), &mut rng).is_none());
+    }
+
+    #[test]
+    fn test_play_game() {
+        let mut game = Game::with_seed(1);
+        game.start_game();
+        let mut agent = RolloutAgent::with_seed(20, 10, 1);
+        agent.set_playout(Playout::Greedy);
+        agent.set_threads(2);
+        while game.get_board().max_tile() < 256 {
+            let dir = agent.best_move(game.get_board()).unwrap();
+            game.action(dir);
+            assert_eq!(game.get_status(), GameStatus::Playing);
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use crate::game::{GameStatus, Snapshot};
+use crate::tile::Board;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{Display, Formatter};
+
+/// The first word of a saved game
+pub const SAVE_HEADER: &str = "wasm_terminal_2048";
+
+/// The version of the save format written by `Game::save`
+pub const SAVE_VERSION: u32 = 1;
+
+/// Errors of loading a saved game
+///
+/// * `InvalidHeader` The first line is not the header of a saved game
+/// * `UnsupportedVersion` The saved game is written by an unsupported version of the format
+/// * `MissingField` A required field is not found
+/// * `InvalidLine` A line is malformed, with the line number (from 1) and the reason
+/// * `Inconsistent` The fields contradict each other, with the reason
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    InvalidHeader,
+    UnsupportedVersion(u32),
+    MissingField(&'static str),
+    InvalidLine { line: usize, reason: String },
+    Inconsistent(String),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            LoadError::InvalidHeader => write!(f, "not a saved game"),
+            LoadError::UnsupportedVersion(version) =>
+                write!(f, "unsupported save version {}, expected {}", version, SAVE_VERSION),
+            LoadError::MissingField(name) => write!(f, "missing field `{}`", name),
+            LoadError::InvalidLine { line, reason } => write!(f, "line {}: {}", line, reason),
+            LoadError::Inconsistent(reason) => write!(f, "inconsistent save, {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// All the state of a game that is saved. The format is line based and human-readable,
+/// the first line is the header with the version, and each following line is a field name
+/// and its value separated by a space. For example,
+///
+/// ```text
+/// wasm_terminal_2048 save 1
+/// seed 42
+/// best_score 120
+/// undo_limit none
+/// undos_left none
+/// history_size 100
+/// current 3 8 playing <rng> 4x4:2,4,0,0,0,0,0,0,0,0,0,0,0,0,0,0
+/// undo 2 4 playing <rng> 4x4:4,0,0,0,2,0,0,0,0,0,0,0,0,0,0,0
+/// ```
+///
+/// `current`, `undo` and `redo` are snapshots of steps, score, status, the random generator
+/// and the board. `undo` lines are from the oldest to the latest, and `redo` lines are from
+/// the last to the next redone one. The random generator is written as
+/// `<seed in hex>:<stream>:<word position>`.
+pub(crate) struct SaveData {
+    pub(crate) seed: u64,
+    pub(crate) best_score: u32,
+    pub(crate) undo_limit: Option<u32>,
+    pub(crate) undos_left: Option<u32>,
+    pub(crate) history_size: usize,
+    pub(crate) current: Snapshot,
+    pub(crate) undo_history: Vec<Snapshot>,
+    pub(crate) redo_history: Vec<Snapshot>,
+}
+
+/// Get the name of the game status in the save format
+fn status_name(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Playing => "playing",
+        GameStatus::Won => "won",
+        GameStatus::WonContinuing => "won_continuing",
+        GameStatus::Lost => "lost",
+    }
+}
+
+/// Get the game status by the name in the save format
+fn parse_status(name: &str) -> Result<GameStatus, String> {
+    match name {
+        "playing" => Ok(GameStatus::Playing),
+        "won" => Ok(GameStatus::Won),
+        "won_continuing" => Ok(GameStatus::WonContinuing),
+        "lost" => Ok(GameStatus::Lost),
+        _ => Err(format!("invalid status `{}`", name)),
+    }
+}
+
+/// Parse a number, the name of the field is used in the error
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid {} `{}`", name, value))
+}
+
+/// Parse an optional number, `none` means no value
+fn parse_optional(name: &str, value: &str) -> Result<Option<u32>, String> {
+    if value == "none" {
+        Ok(None)
+    } else {
+        parse_number(name, value).map(Some)
+    }
+}
+
+/// Set the value of a field that appears once
+fn set_once<T>(field: &mut Option<T>, name: &str, value: T) -> Result<(), String> {
+    if field.is_some() {
+        return Err(format!("duplicate field `{}`", name));
+    }
+    *field = Some(value);
+    Ok(())
+}
+
+fn optional_text(value: Option<u32>) -> String {
+    value.map_or_else(|| "none".to_string(), |value| value.to_string())
+}
+
+fn rng_text(rng: &ChaCha8Rng) -> String {
+    let seed: String = rng.get_seed().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}:{}:{}", seed, rng.get_stream(), rng.get_word_pos())
+}
+
+fn parse_rng(text: &str) -> Result<ChaCha8Rng, String> {
+    let invalid = || format!("invalid random generator `{}`", text);
+    let mut parts = text.split(':');
+    let (seed_text, stream, word_pos) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(seed), Some(stream), Some(word_pos), None) => (seed, stream, word_pos),
+        _ => return Err(invalid()),
+    };
+    if seed_text.len() != 64 || !seed_text.is_ascii() {
+        return Err(invalid());
+    }
+    let mut seed = [0; 32];
+    for (idx, byte) in seed.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&seed_text[2 * idx..2 * idx + 2], 16).map_err(|_| invalid())?;
+    }
+    let mut rng = ChaCha8Rng::from_seed(seed);
+    rng.set_stream(stream.parse().map_err(|_| invalid())?);
+    rng.set_word_pos(word_pos.parse().map_err(|_| invalid())?);
+    Ok(rng)
+}
+
+fn board_text(board: &Board) -> String {
+    let values: Vec<String> = board.get_values().iter().map(|value| value.to_string()).collect();
+    format!("{}x{}:{}", board.get_rows(), board.get_cols(), values.join(","))
+}
+
+fn parse_board(text: &str) -> Result<Board, String> {
+    let invalid = |reason: &str| format!("invalid board `{}`, {}", text, reason);
+    let (size, values) = text.split_once(':').ok_or_else(|| invalid("missing size"))?;
+    let (rows, cols) = size.split_once('x').ok_or_else(|| invalid("invalid size"))?;
+    let rows: usize = rows.parse().map_err(|_| invalid("invalid size"))?;
+    let cols: usize = cols.parse().map_err(|_| invalid("invalid size"))?;
+    let values = values.split(',')
+        .map(|value| value.parse().map_err(|_| invalid(&format!("invalid tile `{}`", value))))
+        .collect::<Result<Vec<u32>, String>>()?;
+    Board::from_values(rows, cols, values).map_err(|err| invalid(&err.to_string()))
+}
+
+fn snapshot_text(snapshot: &Snapshot) -> String {
+    format!("{} {} {} {} {}", snapshot.steps, snapshot.score, status_name(snapshot.status),
+        rng_text(&snapshot.rng), board_text(&snapshot.board))
+}
+
+fn parse_snapshot(value: &str) -> Result<Snapshot, String> {
+    let parts: Vec<&str> = value.split(' ').collect();
+    if parts.len() != 5 {
+        return Err(format!("expected 5 values of a snapshot, found {}", parts.len()));
+    }
+    Ok(Snapshot {
+        steps: parse_number("steps", parts[0])?,
+        score: parse_number("score", parts[1])?,
+        status: parse_status(parts[2])?,
+        rng: parse_rng(parts[3])?,
+        board: parse_board(parts[4])?,
+    })
+}
+
+impl SaveData {
+    /// Write the state in the save format
+    pub(crate) fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!("{} save {}", SAVE_HEADER, SAVE_VERSION),
+            format!("seed {}", self.seed),
+            format!("best_score {}", self.best_score),
+            format!("undo_limit {}", optional_text(self.undo_limit)),
+            format!("undos_left {}", optional_text(self.undos_left)),
+            format!("history_size {}", self.history_size),
+            format!("current {}", snapshot_text(&self.current)),
+        ];
+        lines.extend(self.undo_history.iter().map(|snapshot| format!("undo {}", snapshot_text(snapshot))));
+        lines.extend(self.redo_history.iter().map(|snapshot| format!("redo {}", snapshot_text(snapshot))));
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    /// Read the state from the save format. Empty lines are ignored.
+    ///
+    /// # Arguments
+    /// * `text` The saved game
+    ///
+    pub(crate) fn parse(text: &str) -> Result<Self, LoadError> {
+        let mut lines = text.lines().enumerate()
+            .map(|(idx, line)| (idx + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+
+        let (_, header) = lines.next().ok_or(LoadError::InvalidHeader)?;
+        let version = match header.split(' ').collect::<Vec<_>>().as_slice() {
+            [SAVE_HEADER, "save", version] =>
+                version.parse().map_err(|_| LoadError::InvalidHeader)?,
+            _ => return Err(LoadError::InvalidHeader),
+        };
+        if version != SAVE_VERSION {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+
+        let mut seed = None;
+        let mut best_score = None;
+        let mut undo_limit = None;
+        let mut undos_left = None;
+        let mut history_size = None;
+        let mut current = None;
+        let mut undo_history = Vec::new();
+        let mut redo_history = Vec::new();
+        for (line, text) in lines {
+            let (name, value) = text.split_once(' ').unwrap_or((text, ""));
+            let result = match name {
+                "seed" => parse_number(name, value).and_then(|value| set_once(&mut seed, name, value)),
+                "best_score" => parse_number(name, value).and_then(|value| set_once(&mut best_score, name, value)),
+                "undo_limit" => parse_optional(name, value).and_then(|value| set_once(&mut undo_limit, name, value)),
+                "undos_left" => parse_optional(name, value).and_then(|value| set_once(&mut undos_left, name, value)),
+                "history_size" => parse_number(name, value).and_then(|value| set_once(&mut history_size, name, value)),
+                "current" => parse_snapshot(value).and_then(|value| set_once(&mut current, name, value)),
+                "undo" => parse_snapshot(value).map(|value| undo_history.push(value)),
+                "redo" => parse_snapshot(value).map(|value| redo_history.push(value)),
+                _ => Err(format!("unknown field `{}`", name)),
+            };
+            result.map_err(|reason| LoadError::InvalidLine { line, reason })?;
+        }
+
+        let data = SaveData {
+            seed: seed.ok_or(LoadError::MissingField("seed"))?,
+            best_score: best_score.ok_or(LoadError::MissingField("best_score"))?,
+            undo_limit: undo_limit.ok_or(LoadError::MissingField("undo_limit"))?,
+            undos_left: undos_left.ok_or(LoadError::MissingField("undos_left"))?,
+            history_size: history_size.ok_or(LoadError::MissingField("history_size"))?,
+            current: current.ok_or(LoadError::MissingField("current"))?,
+            undo_history,
+            redo_history,
+        };
+        data.validate()?;
+        Ok(data)
+    }
+
+    /// Check the fields agree with each other, so the loaded game keeps the limits of a game
+    pub(crate) fn validate(&self) -> Result<(), LoadError> {
+        let inconsistent = |reason: String| Err(LoadError::Inconsistent(reason));
+        for (name, history) in [("undo", &self.undo_history), ("redo", &self.redo_history)] {
+            if history.len() > self.history_size {
+                return inconsistent(format!("{} {} snapshots exceed the history size {}",
+                    history.len(), name, self.history_size));
+            }
+        }
+        match (self.undo_limit, self.undos_left) {
+            (None, None) => {},
+            (Some(limit), Some(left)) if left <= limit => {},
+            (limit, left) => return inconsistent(format!("{} undos left with the undo limit {}",
+                optional_text(left), optional_text(limit))),
+        }
+        let size = (self.current.board.get_rows(), self.current.board.get_cols());
+        let mut snapshots = self.undo_history.iter().chain(self.redo_history.iter());
+        if let Some(snapshot) = snapshots.find(|snapshot| {
+            (snapshot.board.get_rows(), snapshot.board.get_cols()) != size
+        }) {
+            return inconsistent(format!("the snapshot board {}x{} differs from the current board {}x{}",
+                snapshot.board.get_rows(), snapshot.board.get_cols(), size.0, size.1));
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_text() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2048);
+        rand::RngCore::next_u64(&mut rng);
+        let text = rng_text(&rng);
+        assert_eq!(parse_rng(&text).unwrap(), rng);
+        assert!(parse_rng("00:0:0").is_err());
+        assert!(parse_rng(&format!("{}:x", "0".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn test_board_text() {
+        let board = Board::with_size(2, 3, vec![2, 0, 4, 8, 0, 1 << 20]);
+        let text = board_text(&board);
+        assert_eq!(text, "2x3:2,0,4,8,0,1048576");
+        assert_eq!(parse_board(&text).unwrap(), board);
+        assert!(parse_board("2x3:2,0,4").is_err());
+        assert!(parse_board("2x2:2,0,3,0").is_err());
+        assert!(parse_board("2x2:1,0,0,0").is_err());
+        assert!(parse_board("2,0,4,0").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(SaveData::parse("").err(), Some(LoadError::InvalidHeader));
+        assert_eq!(SaveData::parse("hello\n").err(), Some(LoadError::InvalidHeader));
+        assert_eq!(SaveData::parse("wasm_terminal_2048 save 2\n").err(),
+            Some(LoadError::UnsupportedVersion(2)));
+        assert_eq!(SaveData::parse("wasm_terminal_2048 save 1\nseed 1\n").err(),
+            Some(LoadError::MissingField("best_score")));
+
+        let err = SaveData::parse("wasm_terminal_2048 save 1\n\nseed x\n").err().unwrap();
+        assert_eq!(err, LoadError::InvalidLine { line: 3, reason: "invalid seed `x`".to_string() });
+        assert_eq!(err.to_string(), "line 3: invalid seed `x`");
+        let err = SaveData::parse("wasm_terminal_2048 save 1\nlevel 3\n").err().unwrap();
+        assert_eq!(err.to_string(), "line 2: unknown field `level`");
+        let err = SaveData::parse("wasm_terminal_2048 save 1\nseed 1\nseed 2\n").err().unwrap();
+        assert_eq!(err.to_string(), "line 3: duplicate field `seed`");
+        // the number of tiles overflows
+        let text: Vec<String> = sample_save().lines()
+            .map(|line| match line.strip_prefix("current ") {
+                Some(value) => format!("current {} 18446744073709551615x2:0", value.rsplit_once(' ').unwrap().0),
+                None => line.to_string(),
+            })
+            .collect();
+        let err = SaveData::parse(&text.join("\n")).err().unwrap();
+        assert!(err.to_string().ends_with("the board 18446744073709551615x2 is too large"), "{}", err);
+    }
+
+    /// A saved game with 2 undo snapshots, 1 redo snapshot and 2 undos left of 3
+    fn sample_save() -> String {
+        use crate::game::Game;
+        use crate::tile::Direction;
+
+        let mut game = Game::with_seed(7);
+        game.set_undo_limit(Some(3));
+        game.start_game();
+        for dir in [Direction::Left, Direction::Down, Direction::Right] {
+            game.action(dir);
+        }
+        game.undo();
+        game.save()
+    }
+
+    #[test]
+    fn test_inconsistent() {
+        let text = sample_save();
+        assert!(SaveData::parse(&text).is_ok());
+        let inconsistent = |text: &str| matches!(SaveData::parse(text), Err(LoadError::Inconsistent(_)));
+
+        // the history is larger than its size
+        assert!(inconsistent(&text.replace("history_size 100", "history_size 1")));
+        assert!(SaveData::parse(&text.replace("history_size 100", "history_size 2")).is_ok());
+        let redo = text.lines().find(|line| line.starts_with("redo ")).unwrap();
+        let redos = format!("{}\n{}\n{}", redo, redo, redo);
+        assert!(inconsistent(&text.replace("history_size 100", "history_size 2").replace(redo, &redos)));
+
+        // the undos left exceed the limit
+        assert!(inconsistent(&text.replace("undos_left 2", "undos_left 40")));
+        assert!(inconsistent(&text.replace("undos_left 2", "undos_left none")));
+        assert!(inconsistent(&text.replace("undo_limit 3", "undo_limit none")));
+
+        // a snapshot of another board size
+        let undo = text.lines().find(|line| line.starts_with("undo ")).unwrap();
+        let (prefix, _) = undo.rsplit_once(' ').unwrap();
+        let other = format!("{} 2x2:2,0,0,0", prefix);
+        let err = SaveData::parse(&text.replace(undo, &other)).err().unwrap();
+        assert_eq!(err.to_string(), "inconsistent save, the snapshot board 2x2 differs from the current board 4x4");
+    }
+}
@@ -0,0 +1,288 @@
+use crate::agent::pick;
+use crate::evaluator::{Evaluator, LineHeuristic};
+use crate::spawn::CLASSIC_FOUR_PROBABILITY;
+use crate::tile::{Board, Direction};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// The default depth of the search, which is the number of player movements to look ahead
+pub const DEFAULT_DEPTH: u32 = 3;
+
+/// The value of a board without any movement, which is below any evaluation, so a lost game
+/// is avoided whenever possible
+pub const LOSS_VALUE: f64 = -1e12;
+
+/// The budget is checked once per this number of nodes, since getting the time is not free
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+/// The search is stopped since the budget is used up
+struct BudgetExceeded;
+
+/// `Solver` finds the best movement of a board by the depth-limited expectimax search. Player
+/// nodes take the best movement, and chance nodes take the expected value over all new tiles
+/// generated by the classic rule (see `ClassicSpawn`). Leaves are evaluated by the
+/// `Evaluator`, which is `LineHeuristic` by default.
+///
+/// The search is iterative deepening from depth 1 to the maximum depth, so when the node or
+/// time budget is used up, the best movement of the last completed depth is returned. The
+/// values of chance nodes are cached during a search, since the same board is often reached
+/// by different movements.
+///
+/// Note the time budget is not supported on `wasm32-unknown-unknown`, use the node budget.
+///
+/// ```ignore
+///  let mut solver = Solver::new(3);
+///  solver.set_time_budget(Some(Duration::from_millis(100)));
+///  if let Some(dir) = solver.best_move(game.get_board()) {
+///    game.action(dir);
+///  }
+/// ```
+pub struct Solver<E: Evaluator = LineHeuristic> {
+    evaluator: E,
+    max_depth: u32,
+    node_budget: Option<u64>,
+    time_budget: Option<Duration>,
+    cache: HashMap<(Board, u32), f64>,
+    nodes: u64,
+    deadline: Option<Instant>,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new(DEFAULT_DEPTH)
+    }
+}
+
+impl Solver {
+    /// Create a new `Solver` that looks ahead the given number of movements, without budget
+    ///
+    /// # Arguments
+    /// * `max_depth` The maximum number of player movements to look ahead
+    ///
+    pub fn new(max_depth: u32) -> Self {
+        Self::with_evaluator(LineHeuristic, max_depth)
+    }
+}
+
+impl<E: Evaluator> Solver<E> {
+    /// Create a new `Solver` that evaluates boards by the given evaluator, and looks ahead the
+    /// given number of movements, without budget
+    ///
+    /// # Arguments
+    /// * `evaluator` The evaluator of boards
+    /// * `max_depth` The maximum number of player movements to look ahead
+    ///
+    pub fn with_evaluator(evaluator: E, max_depth: u32) -> Self {
+        Solver {
+            evaluator,
+            max_depth: max_depth.max(1),
+            node_budget: None,
+            time_budget: None,
+            cache: HashMap::new(),
+            nodes: 0,
+            deadline: None,
+        }
+    }
+
+    /// Limit the number of nodes visited by a search, `None` means unlimited
+    ///
+    /// # Arguments
+    /// * `budget` The number of nodes per search
+    ///
+    pub fn set_node_budget(&mut self, budget: Option<u64>) {
+        self.node_budget = budget;
+    }
+
+    /// Limit the time of a search, `None` means unlimited
+    ///
+    /// # Arguments
+    /// * `budget` The time per search
+    ///
+    pub fn set_time_budget(&mut self, budget: Option<Duration>) {
+        self.time_budget = budget;
+    }
+
+    /// Get the evaluator
+    pub fn get_evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    /// Get the number of nodes visited by the last search
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Find the best movement of the given board. Returns `None` if no movement is possible.
+    ///
+    /// # Arguments
+    /// * `board` The board to search
+    ///
+    pub fn best_move(&mut self, board: &Board) -> Option<Direction> {
+        pick(self.evaluate_moves(board).into_iter())
+    }
+
+    /// Get the value of each possible movement of the given board by the deepest completed
+    /// search, or by the evaluator if not even depth 1 is completed
+    ///
+    /// # Arguments
+    /// * `board` The board to search
+    ///
+    pub fn evaluate_moves(&mut self, board: &Board) -> Vec<(Direction, f64)> {
+        self.nodes = 0;
+        self.cache.clear();
+        self.deadline = self.time_budget.map(|budget| Instant::now() + budget);
+
+        let moves: Vec<(Direction, Board)> = Direction::ALL.iter()
+            .filter_map(|dir| board.apply(*dir).map(|(next, _)| (*dir, next)))
+            .collect();
+        let mut best: Vec<(Direction, f64)> = moves.iter()
+            .map(|(dir, next)| (*dir, self.evaluator.evaluate(next)))
+            .collect();
+        for depth in 1..=self.max_depth {
+            let values: Result<Vec<(Direction, f64)>, BudgetExceeded> = moves.iter()
+                .map(|(dir, next)| self.chance_node(next, depth).map(|value| (*dir, value)))
+                .collect();
+            match values {
+                Ok(values) => best = values,
+                Err(BudgetExceeded) => break,
+            }
+        }
+        self.cache.clear();
+        best
+    }
+
+    /// Count a visited node, and check the budget
+    fn visit(&mut self) -> Result<(), BudgetExceeded> {
+        self.nodes += 1;
+        if self.node_budget.is_some_and(|budget| self.nodes > budget) {
+            return Err(BudgetExceeded);
+        }
+        if let Some(deadline) = self.deadline {
+            if self.nodes.is_multiple_of(TIME_CHECK_INTERVAL) && Instant::now() >= deadline {
+                return Err(BudgetExceeded);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the value of the player to move, which is the best value among all movements.
+    /// A board without any movement is valued `LOSS_VALUE`.
+    fn max_node(&mut self, board: &Board, depth: u32) -> Result<f64, BudgetExceeded> {
+        self.visit()?;
+        if depth == 0 {
+            return Ok(if board.can_move() { self.evaluator.evaluate(board) } else { LOSS_VALUE });
+        }
+        let mut best = f64::NEG_INFINITY;
+        for dir in Direction::ALL.iter() {
+            if let Some((next, _)) = board.apply(*dir) {
+                best = f64::max(best, self.chance_node(&next, depth)?);
+            }
+        }
+        Ok(if best == f64::NEG_INFINITY { LOSS_VALUE } else { best })
+    }
+
+    /// Get the expected value after a new tile is generated on the moved board
+    fn chance_node(&mut self, board: &Board, depth: u32) -> Result<f64, BudgetExceeded> {
+        if let Some(value) = self.cache.get(&(board.clone(), depth)) {
+            return Ok(*value);
+        }
+        let empty_tiles = board.empty_tiles();
+        if empty_tiles.is_empty() {
+            return self.max_node(board, depth - 1);
+        }
+        let four_probability = f64::from(CLASSIC_FOUR_PROBABILITY);
+        let mut value = 0.0;
+        for idx in empty_tiles.iter() {
+            value += (1.0 - four_probability) * self.max_node(&board.with_tile_value(*idx, 2), depth - 1)?;
+            value += four_probability * self.max_node(&board.with_tile_value(*idx, 4), depth - 1)?;
+        }
+        value /= empty_tiles.len() as f64;
+        self.cache.insert((board.clone(), depth), value);
+        Ok(value)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Game, GameStatus};
+
+    #[test]
+    fn test_best_move_merge() {
+        // only left and right merge the two 1024 tiles without losing the corner
+        let board: Board = "1024,1024,0,0/2,4,0,0/0,0,0,0/0,0,0,0".parse().unwrap();
+        let dir = Solver::new(2).best_move(&board).unwrap();
+        assert!(dir == Direction::Left || dir == Direction::Right);
+
+        let values = Solver::new(2).evaluate_moves(&board);
+        assert_eq!(values.len(), 3);
+        let best = values.iter().map(|(_, value)| *value).fold(f64::MIN, f64::max);
+        assert!(values.contains(&(dir, best)));
+    }
+
+    #[test]
+    fn test_best_move_no_move() {
+        let board: Board = "2,4/4,2".parse().unwrap();
+        assert_eq!(Solver::default().best_move(&board), None);
+        // a legal movement is chosen even without budget
+        let board: Board = "2,4/0,2".parse().unwrap();
+        let mut solver = Solver::new(3);
+        solver.set_node_budget(Some(0));
+        assert!(board.legal_moves().contains(&solver.best_move(&board).unwrap()));
+    }
+
+    #[test]
+    fn test_loss_value() {
+        let mut solver = Solver::new(2);
+        let lost: Board = "2,4/4,2".parse().unwrap();
+        assert_eq!(solver.max_node(&lost, 0).ok(), Some(LOSS_VALUE));
+        assert_eq!(solver.max_node(&lost, 2).ok(), Some(LOSS_VALUE));
+
+        // the evaluation of big tiles is negative, but still better than a lost game
+        let board: Board = "32768,16384,8192,4096/32768,16384,8192,4096/2,0,0,0/0,0,0,0".parse().unwrap();
+        assert!(solver.get_evaluator().evaluate(&board) < 0.0);
+        let values = Solver::new(1).evaluate_moves(&board);
+        assert!(values.iter().all(|(_, value)| *value > LOSS_VALUE));
+        assert!(solver.max_node(&board, 1).ok().unwrap() > LOSS_VALUE);
+    }
+
+    #[test]
+    fn test_node_budget() {
+        let board: Board = "2,0,0,2/0,4,0,0/0,0,8,0/2,0,0,0".parse().unwrap();
+        let mut solver = Solver::new(10);
+        solver.set_node_budget(Some(5000));
+        assert!(solver.best_move(&board).is_some());
+        assert!(solver.get_nodes() <= 5001);
+
+        let mut solver = Solver::new(3);
+        solver.set_time_budget(Some(Duration::from_millis(1)));
+        assert!(solver.best_move(&board).is_some());
+    }
+
+    #[test]
+    fn test_evaluator() {
+        use crate::evaluator::{EmptyTiles, Weighted};
+
+        // keep the most empty tiles
+        let board: Board = "2,2,4,0/0,0,0,0/0,0,0,0/0,0,0,0".parse().unwrap();
+        let mut weighted = Weighted::new(100.0);
+        weighted.add(1.0, EmptyTiles);
+        let mut solver = Solver::with_evaluator(weighted, 1);
+        assert_eq!(solver.get_evaluator().evaluate(&board), 113.0);
+        let dir = solver.best_move(&board).unwrap();
+        assert!(dir == Direction::Left || dir == Direction::Right);
+    }
+
+    #[test]
+    fn test_play_game() {
+        let mut game = Game::with_seed(2048);
+        game.start_game();
+        let mut solver = Solver::new(2);
+        while game.get_board().max_tile() < 512 {
+            let dir = solver.best_move(game.get_board()).unwrap();
+            game.action(dir);
+            assert_eq!(game.get_status(), GameStatus::Playing);
+        }
+    }
+}
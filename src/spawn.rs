@@ -0,0 +1,291 @@
+use crate::tile::{Board, Direction};
+use rand::{Rng, RngCore};
+
+/// `SpawnPolicy` decides where and which new tiles are generated after each movement.
+/// `Game` is parameterized over the policy, so the rule of tile generation can be changed
+/// without touching the game logic, e.g. to build difficulty levels.
+///
+pub trait SpawnPolicy {
+    /// Generate new tiles on the board by the given random generator. If return values is
+    /// false, it means no tile is generated since there is no empty tile.
+    ///
+    /// # Arguments
+    /// * `board` The board to generate new tiles on
+    /// * `rng` The random generator
+    ///
+    fn spawn(&mut self, board: &mut Board, rng: &mut dyn RngCore) -> bool;
+}
+
+impl<P: SpawnPolicy + ?Sized> SpawnPolicy for Box<P> {
+    fn spawn(&mut self, board: &mut Board, rng: &mut dyn RngCore) -> bool {
+        (**self).spawn(board, rng)
+    }
+}
+
+/// Set a random empty tile to 4 with the given probability, otherwise 2
+fn spawn_random_tile(board: &mut Board, rng: &mut dyn RngCore, four_probability: f32) -> bool {
+    let empty_tiles = board.empty_tiles();
+    if empty_tiles.is_empty() {
+        return false;
+    }
+    // choose zero cell
+    let idx = empty_tiles[rng.gen_range(0..empty_tiles.len())];
+    // choose value 2 or 4
+    let r: f32 = rng.gen();
+    let value = if r < four_probability {4} else {2};
+    board.set_tile_value(idx, value);
+    true
+}
+
+/// The probability of generating 4 by the classic rule
+pub const CLASSIC_FOUR_PROBABILITY: f32 = 0.2;
+
+/// The classic rule: a uniformly chosen empty tile is set to 2 (80%) or 4 (20%)
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClassicSpawn;
+
+impl SpawnPolicy for ClassicSpawn {
+    fn spawn(&mut self, board: &mut Board, rng: &mut dyn RngCore) -> bool {
+        spawn_random_tile(board, rng, CLASSIC_FOUR_PROBABILITY)
+    }
+}
+
+/// A uniformly chosen empty tile is set to 4 with the configured probability, otherwise 2
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WeightedSpawn {
+    four_probability: f32,
+}
+
+impl WeightedSpawn {
+    /// Create a new policy by the probability of generating 4, which is clamped to `0.0..=1.0`
+    pub fn new(four_probability: f32) -> Self {
+        Self {
+            four_probability: four_probability.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Get the probability of generating 4
+    pub fn four_probability(&self) -> f32 {
+        self.four_probability
+    }
+}
+
+impl SpawnPolicy for WeightedSpawn {
+    fn spawn(&mut self, board: &mut Board, rng: &mut dyn RngCore) -> bool {
+        spawn_random_tile(board, rng, self.four_probability)
+    }
+}
+
+/// A uniformly chosen empty tile is always set to 2
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnlyTwosSpawn;
+
+impl SpawnPolicy for OnlyTwosSpawn {
+    fn spawn(&mut self, board: &mut Board, rng: &mut dyn RngCore) -> bool {
+        spawn_random_tile(board, rng, 0.0)
+    }
+}
+
+/// Generate several tiles per turn by the inner policy. Fewer tiles are generated when the
+/// board runs out of empty tiles.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiSpawn<P> {
+    inner: P,
+    count: usize,
+}
+
+impl<P: SpawnPolicy> MultiSpawn<P> {
+    /// Create a new policy that generates `count` tiles per turn by the `inner` policy
+    pub fn new(inner: P, count: usize) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl<P: SpawnPolicy> SpawnPolicy for MultiSpawn<P> {
+    fn spawn(&mut self, board: &mut Board, rng: &mut dyn RngCore) -> bool {
+        let mut spawned = false;
+        for _ in 0..self.count {
+            if !self.inner.spawn(board, rng) {
+                break;
+            }
+            spawned = true;
+        }
+        spawned
+    }
+}
+
+/// An adversarial policy that places the worst tile for the player. Every empty tile with
+/// either 2 or 4 is tried, and the one that leaves the player the fewest empty tiles after
+/// the best reply is chosen. The random generator only breaks ties.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdversarialSpawn;
+
+impl AdversarialSpawn {
+    /// Evaluate the board from the player's view: the most empty tiles after any movement,
+    /// with the score of the movement to break ties. Returns `None` if no movement is possible.
+    fn player_value(board: &Board) -> Option<(usize, u32)> {
+        Direction::ALL.iter()
+            .filter_map(|dir| board.apply(*dir))
+            .map(|(next, info)| (next.empty_tiles().len(), info.score))
+            .max()
+    }
+}
+
+impl SpawnPolicy for AdversarialSpawn {
+    fn spawn(&mut self, board: &mut Board, rng: &mut dyn RngCore) -> bool {
+        let mut candidates = Vec::new();
+        let mut worst = None;
+        for idx in board.empty_tiles() {
+            for value in [2, 4].iter() {
+                let player_value = Self::player_value(&board.with_tile_value(idx, *value));
+                if worst.is_none() || player_value < worst.unwrap() {
+                    worst = Some(player_value);
+                    candidates.clear();
+                }
+                if Some(player_value) == worst {
+                    candidates.push((idx, *value));
+                }
+            }
+        }
+
+        if candidates.is_empty() {
+            return false;
+        }
+        let (idx, value) = candidates[rng.gen_range(0..candidates.len())];
+        board.set_tile_value(idx, value);
+        true
+    }
+}
+
+/// Difficulty levels built from the spawn policies
+///
+/// * `Easy` Only 2 is generated, see `OnlyTwosSpawn`
+/// * `Normal` The classic rule, see `ClassicSpawn`
+/// * `Hard` 2 and 4 are generated with the same probability, see `WeightedSpawn`
+/// * `Expert` The worst tile for the player is generated, see `AdversarialSpawn`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Expert,
+}
+
+impl Difficulty {
+    /// All difficulty levels from the easiest to the hardest
+    pub const ALL: [Difficulty; 4] =
+        [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Expert];
+
+    /// Get the difficulty level by the case-insensitive name, e.g. `hard`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|level| level.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Get the name of the difficulty level
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Expert => "Expert",
+        }
+    }
+
+    /// Get the spawn policy of the difficulty level
+    pub fn policy(self) -> Box<dyn SpawnPolicy + Send> {
+        match self {
+            Difficulty::Easy => Box::new(OnlyTwosSpawn),
+            Difficulty::Normal => Box::new(ClassicSpawn),
+            Difficulty::Hard => Box::new(WeightedSpawn::new(0.5)),
+            Difficulty::Expert => Box::new(AdversarialSpawn),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// Fill an empty board by the given policy, and return the generated values
+    fn fill_board<P: SpawnPolicy>(policy: &mut P) -> Vec<u32> {
+        let mut board = Board::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        while policy.spawn(&mut board, &mut rng) {}
+        board.get_values()
+    }
+
+    #[test]
+    fn test_classic_spawn() {
+        let values = fill_board(&mut ClassicSpawn);
+        assert!(values.iter().all(|val| *val == 2 || *val == 4));
+    }
+
+    #[test]
+    fn test_weighted_spawn() {
+        let values = fill_board(&mut WeightedSpawn::new(1.0));
+        assert!(values.iter().all(|val| *val == 4));
+        let values = fill_board(&mut WeightedSpawn::new(-1.0));
+        assert!(values.iter().all(|val| *val == 2));
+        assert_eq!(WeightedSpawn::new(2.0).four_probability(), 1.0);
+    }
+
+    #[test]
+    fn test_only_twos_spawn() {
+        let values = fill_board(&mut OnlyTwosSpawn);
+        assert!(values.iter().all(|val| *val == 2));
+    }
+
+    #[test]
+    fn test_multi_spawn() {
+        let mut board = Board::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut policy = MultiSpawn::new(ClassicSpawn, 3);
+        assert!(policy.spawn(&mut board, &mut rng));
+        assert_eq!(board.empty_tiles().len(), 13);
+        for _ in 0..4 {
+            assert!(policy.spawn(&mut board, &mut rng));
+        }
+        assert_eq!(board.empty_tiles().len(), 1);
+        // only one tile is generated on the last empty tile
+        assert!(policy.spawn(&mut board, &mut rng));
+        assert_eq!(board.empty_tiles().len(), 0);
+        assert!(!policy.spawn(&mut board, &mut rng));
+    }
+
+    #[test]
+    fn test_adversarial_spawn() {
+        // 2 on the last empty tile can be merged, but 4 ends the game
+        let values = vec![
+                8, 16, 2, 0,
+                2, 4, 8, 16,
+                4, 2, 16, 2,
+                2, 4, 2, 4,
+            ];
+        let mut board = Board::new(values);
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        assert!(AdversarialSpawn.spawn(&mut board, &mut rng));
+        assert_eq!(board.get_values()[3], 4);
+        assert!(!board.can_move());
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let mut policy = Difficulty::Easy.policy();
+        let values = fill_board(&mut policy);
+        assert!(values.iter().all(|val| *val == 2));
+        assert_eq!(Difficulty::default(), Difficulty::Normal);
+        assert_eq!(Difficulty::from_name("expert"), Some(Difficulty::Expert));
+        assert_eq!(Difficulty::from_name("Hard"), Some(Difficulty::Hard));
+        assert_eq!(Difficulty::from_name("impossible"), None);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::tile::{Board, Direction};
+
+/// The 8 symmetries of a board, which are the rotations and reflections. A board and its
+/// transformed board are equivalent for the game, and a movement toward a direction on the
+/// board is the same as the movement toward the mapped direction on the transformed board.
+///
+/// * `Identity` The board is not changed
+/// * `RotateCw` Rotate clockwise by a quarter turn
+/// * `Rotate180` Rotate by a half turn
+/// * `RotateCcw` Rotate counterclockwise by a quarter turn
+/// * `ReflectHorizontal` Reflect left to right
+/// * `ReflectVertical` Reflect top to bottom
+/// * `Transpose` Reflect along the main diagonal, so rows become columns
+/// * `AntiTranspose` Reflect along the anti-diagonal
+///
+/// ```ignore
+///  // find a movement on the canonical board, and replay it on the original board
+///  let (canonical, transform) = board.canonical();
+///  let dir = find_best_move(&canonical);
+///  game.action(transform.inverse().map_direction(dir));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transform {
+    Identity,
+    RotateCw,
+    Rotate180,
+    RotateCcw,
+    ReflectHorizontal,
+    ReflectVertical,
+    Transpose,
+    AntiTranspose,
+}
+
+impl Transform {
+    /// All the symmetries of a board
+    pub const ALL: [Transform; 8] = [
+        Transform::Identity,
+        Transform::RotateCw,
+        Transform::Rotate180,
+        Transform::RotateCcw,
+        Transform::ReflectHorizontal,
+        Transform::ReflectVertical,
+        Transform::Transpose,
+        Transform::AntiTranspose,
+    ];
+
+    /// Get the transformed board. Quarter turns and transposes swap the number of rows and
+    /// columns.
+    ///
+    /// # Arguments
+    /// * `board` The board to transform
+    ///
+    pub fn apply(self, board: &Board) -> Board {
+        let mut board = board.clone();
+        match self {
+            Transform::Identity => {},
+            Transform::RotateCw => board.rotate_cw(),
+            Transform::Rotate180 => {
+                board.reflect_horizontal();
+                board.reflect_vertical();
+            },
+            Transform::RotateCcw => board.rotate_ccw(),
+            Transform::ReflectHorizontal => board.reflect_horizontal(),
+            Transform::ReflectVertical => board.reflect_vertical(),
+            Transform::Transpose => board.transpose(),
+            Transform::AntiTranspose => {
+                board.transpose();
+                board.reflect_horizontal();
+                board.reflect_vertical();
+            },
+        }
+        board
+    }
+
+    /// Map a direction on the board to the direction on the transformed board, e.g. `Up`
+    /// becomes `Right` by `RotateCw`
+    ///
+    /// # Arguments
+    /// * `dir` The direction on the board
+    ///
+    pub fn map_direction(self, dir: Direction) -> Direction {
+        match self {
+            Transform::Identity => dir,
+            Transform::RotateCw => dir.rotate_cw(),
+            Transform::Rotate180 => dir.opposite(),
+            Transform::RotateCcw => dir.rotate_ccw(),
+            Transform::ReflectHorizontal => dir.reflect_horizontal(),
+            Transform::ReflectVertical => dir.reflect_vertical(),
+            Transform::Transpose => dir.transpose(),
+            Transform::AntiTranspose => dir.transpose().opposite(),
+        }
+    }
+
+    /// Get the transform that restores the transformed board
+    pub fn inverse(self) -> Self {
+        match self {
+            Transform::RotateCw => Transform::RotateCcw,
+            Transform::RotateCcw => Transform::RotateCw,
+            _ => self,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_board() -> Board {
+        Board::with_size(2, 3, vec![
+                2, 4, 8,
+                16, 0, 32,
+            ])
+    }
+
+    #[test]
+    fn test_apply() {
+        let board = sample_board();
+        let expected = [
+            (Transform::Identity, "2,4,8/16,0,32"),
+            (Transform::RotateCw, "16,2/0,4/32,8"),
+            (Transform::Rotate180, "32,0,16/8,4,2"),
+            (Transform::RotateCcw, "8,32/4,0/2,16"),
+            (Transform::ReflectHorizontal, "8,4,2/32,0,16"),
+            (Transform::ReflectVertical, "16,0,32/2,4,8"),
+            (Transform::Transpose, "2,16/4,0/8,32"),
+            (Transform::AntiTranspose, "32,8/0,4/16,2"),
+        ];
+        for (transform, notation) in expected.iter() {
+            let transformed = transform.apply(&board);
+            assert_eq!(format!("{:#}", transformed), *notation, "{:?}", transform);
+            assert_eq!(transform.inverse().apply(&transformed), board, "{:?}", transform);
+        }
+    }
+
+    #[test]
+    fn test_map_direction() {
+        // the movement on the board equals to the mapped movement on the transformed board
+        let board: Board = "2,2,0,4/0,8,8,0/4,0,2,2/0,16,0,2".parse().unwrap();
+        for transform in Transform::ALL.iter() {
+            let transformed = transform.apply(&board);
+            for dir in Direction::ALL.iter() {
+                let (moved, info) = board.apply(*dir).unwrap();
+                let (expected, expected_info) = transformed.apply(transform.map_direction(*dir)).unwrap();
+                assert_eq!(transform.apply(&moved), expected, "{:?} {:?}", transform, dir);
+                assert_eq!(info, expected_info);
+                assert_eq!(transform.inverse().map_direction(transform.map_direction(*dir)), *dir);
+            }
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        let board: Board = "0,2,0,0/0,0,0,4/0,0,0,0/8,0,0,0".parse().unwrap();
+        let (canonical, transform) = board.canonical();
+        assert_eq!(transform.apply(&board), canonical);
+        for other in Transform::ALL.iter() {
+            assert_eq!(other.apply(&board).canonical().0, canonical);
+        }
+        assert_ne!("2,0,0,0/0,0,0,0/0,0,0,0/0,0,0,4".parse::<Board>().unwrap().canonical().0, canonical);
+
+        // a move found on the canonical board is replayed on the original board
+        let (expected, _) = canonical.apply(Direction::Left).unwrap();
+        let (moved, _) = board.apply(transform.inverse().map_direction(Direction::Left)).unwrap();
+        assert_eq!(transform.apply(&moved), expected);
+    }
+}
@@ -1,38 +1,311 @@
 use transpose;
-use rand::Rng;
-use rand::prelude::ThreadRng;
+use crate::bitboard::{BitBoard, MAX_BIT_EXPONENT};
+use crate::spawn::{ClassicSpawn, SpawnPolicy};
+use crate::symmetry::Transform;
+use rand::RngCore;
+use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
+use std::ops::AddAssign;
+use std::str::FromStr;
 
 
 /// Directions of moving tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     Left = 0, Right = 1, Up = 2, Down
 }
 
-/// `Board` includes 16 tile values and a random generator for a new tile gneration. 
-/// Also, it includes methods for the tile movement and how to display a board 
-/// with internal tiles.
+/// Error of parsing or converting a direction, with the invalid input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectionError(pub String);
+
+impl Display for DirectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "invalid direction `{}`", self.0)
+    }
+}
+
+impl std::error::Error for DirectionError {}
+
+impl Direction {
+    /// All directions in the order of their codes
+    pub const ALL: [Direction; 4] = [Direction::Left, Direction::Right, Direction::Up, Direction::Down];
+
+    /// Get the name of the direction
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::Left => "Left",
+            Direction::Right => "Right",
+            Direction::Up => "Up",
+            Direction::Down => "Down",
+        }
+    }
+
+    /// Get the opposite direction
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
+
+    /// Get the direction after rotating the board clockwise by a quarter turn, e.g. `Up`
+    /// becomes `Right`
+    pub fn rotate_cw(self) -> Self {
+        match self {
+            Direction::Left => Direction::Up,
+            Direction::Up => Direction::Right,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Left,
+        }
+    }
+
+    /// Get the direction after rotating the board counterclockwise by a quarter turn, e.g.
+    /// `Up` becomes `Left`
+    pub fn rotate_ccw(self) -> Self {
+        self.rotate_cw().opposite()
+    }
+
+    /// Get the direction after reflecting the board left to right, which swaps `Left` and `Right`
+    pub fn reflect_horizontal(self) -> Self {
+        match self {
+            Direction::Left | Direction::Right => self.opposite(),
+            Direction::Up | Direction::Down => self,
+        }
+    }
+
+    /// Get the direction after reflecting the board top to bottom, which swaps `Up` and `Down`
+    pub fn reflect_vertical(self) -> Self {
+        match self {
+            Direction::Left | Direction::Right => self,
+            Direction::Up | Direction::Down => self.opposite(),
+        }
+    }
+
+    /// Get the direction after transposing the board, which swaps `Left` and `Up`, and `Right`
+    /// and `Down`
+    pub fn transpose(self) -> Self {
+        match self {
+            Direction::Left => Direction::Up,
+            Direction::Up => Direction::Left,
+            Direction::Right => Direction::Down,
+            Direction::Down => Direction::Right,
+        }
+    }
+}
+
+/// Parse a direction by the case-insensitive name, e.g. `left`, or the vim keys `h-j-k-l`,
+/// or the WASD keys `a-s-w-d`
+impl FromStr for Direction {
+    type Err = DirectionError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_ascii_lowercase().as_str() {
+            "left" | "h" | "a" => Ok(Direction::Left),
+            "right" | "l" | "d" => Ok(Direction::Right),
+            "up" | "k" | "w" => Ok(Direction::Up),
+            "down" | "j" | "s" => Ok(Direction::Down),
+            _ => Err(DirectionError(text.to_string())),
+        }
+    }
+}
+
+/// Convert the code of a direction, e.g. 0 for `Left`, which is used by the front-ends
+impl TryFrom<i32> for Direction {
+    type Error = DirectionError;
+
+    fn try_from(code: i32) -> Result<Self, Self::Error> {
+        usize::try_from(code).ok()
+            .and_then(|idx| Direction::ALL.get(idx).copied())
+            .ok_or_else(|| DirectionError(code.to_string()))
+    }
+}
+
+/// Outcome of a tile movement
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MoveInfo {
+    /// Whether any tile is moved or merged
+    pub moved: bool,
+    /// The number of merges
+    pub merges: u32,
+    /// The score gained by merges, which is the sum of the merged tile values
+    pub score: u32,
+}
+
+impl AddAssign for MoveInfo {
+    fn add_assign(&mut self, other: Self) {
+        self.moved |= other.moved;
+        self.merges += other.merges;
+        self.score += other.score;
+    }
+}
+
+/// `Board` includes the tile values of a board with the given number of rows and columns
+/// (4x4 by default). Tile values are stored row by row as exponents, e.g. 1 for the tile 2,
+/// 11 for the tile 2048 and 0 for an empty tile, so tiles never overflow. Also, it includes
+/// methods for the tile movement and how to display a board with internal tiles.
+///
+/// `Board` is a plain value without a random generator, so it can be cloned, compared, hashed
+/// and sent across threads, e.g. for search trees. The random generator for a new tile
+/// generation is given by the caller, which is owned by `Game`.
 ///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "BoardData"))]
 pub struct Board {
-    values: Vec<u16>,
-    rng: ThreadRng,
+    exponents: Vec<u8>,
+    rows: usize,
+    cols: usize,
 }
 
 impl Default for Board {
     fn default() -> Self {
-        Self {
-            values : vec![0; 16], 
-            rng: rand::thread_rng(),
+        Self::new(vec![0; 16])
+    }
+}
+
+/// Errors of building a board
+///
+/// * `Empty` No tile is given, or the number of rows or columns is 0
+/// * `InvalidTile` A tile is not 0 or a power of two from 2, with the text of the tile
+/// * `RaggedRows` Rows have different numbers of tiles
+/// * `TileCount` The number of tiles does not match the size of the board
+/// * `NotSquare` The number of tiles can not make a square board
+/// * `InvalidIndex` The index of a tile is out of the board, with the number of tiles
+/// * `TooLarge` The number of tiles of the rows and columns overflows `usize`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardError {
+    Empty,
+    InvalidTile(String),
+    RaggedRows,
+    TileCount { expected: usize, found: usize },
+    NotSquare(usize),
+    InvalidIndex { index: usize, len: usize },
+    TooLarge { rows: usize, cols: usize },
+}
+
+impl Display for BoardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            BoardError::Empty => write!(f, "no tile is given"),
+            BoardError::InvalidTile(tile) => write!(f, "invalid tile `{}`", tile),
+            BoardError::RaggedRows => write!(f, "rows have different numbers of tiles"),
+            BoardError::TileCount { expected, found } =>
+                write!(f, "expected {} tiles, found {}", expected, found),
+            BoardError::NotSquare(len) => write!(f, "{} tiles can not make a square board", len),
+            BoardError::InvalidIndex { index, len } =>
+                write!(f, "tile index {} is out of the board of {} tiles", index, len),
+            BoardError::TooLarge { rows, cols } => write!(f, "the board {}x{} is too large", rows, cols),
         }
     }
 }
 
+impl std::error::Error for BoardError {}
+
+/// The board is drawn with colors and box characters by default, and the alternate form
+/// `{:#}` writes the compact notation, which is parsed back by `FromStr`, e.g.
+/// `0,2,512,16/0,2,8,16/4,4,2,32/2,0,4,32`. Each row of a single column board ends with `,`,
+/// e.g. `2,/4,/16,`, so it is not parsed as exponents.
 impl Display for Board {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-         self.draw(f)
+        if f.alternate() {
+            self.write_notation(f)
+        } else {
+            self.draw(f)
+        }
     }
 }
 
+/// Parse the compact notation of a board, which has rows separated by `/`. Tiles in a row are
+/// either values separated by `,`, e.g. `0,2,512,16/0,2,8,16/4,4,2,32/2,0,4,32`, or exponents
+/// as hex digits without `,`, e.g. `0194/0134/2215/1025`. The exponent form is used only if
+/// there is no `,` at all. A row of values may end with `,`, e.g. `2,/4,/16,`.
+impl FromStr for Board {
+    type Err = BoardError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(BoardError::Empty);
+        }
+        let hex = !text.contains(',');
+        let rows = text.split('/')
+            .map(|row| if hex {parse_hex_row(row.trim())} else {parse_value_row(row.trim())})
+            .collect::<Result<Vec<Vec<u32>>, BoardError>>()?;
+        let cols = rows[0].len();
+        if rows.iter().any(|row| row.len() != cols) {
+            return Err(BoardError::RaggedRows);
+        }
+        Board::from_values(rows.len(), cols, rows.concat())
+    }
+}
+
+/// Build a square board from tile values, e.g. 16 values for a 4x4 board
+impl TryFrom<Vec<u32>> for Board {
+    type Error = BoardError;
+
+    fn try_from(values: Vec<u32>) -> Result<Self, Self::Error> {
+        let size = square_size(values.len());
+        if size * size != values.len() {
+            return Err(BoardError::NotSquare(values.len()));
+        }
+        Board::from_values(size, size, values)
+    }
+}
+
+/// The fields of a deserialized `Board`, which are validated as `Board::from_values`
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct BoardData {
+    exponents: Vec<u8>,
+    rows: usize,
+    cols: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<BoardData> for Board {
+    type Error = BoardError;
+
+    fn try_from(data: BoardData) -> Result<Self, Self::Error> {
+        if let Some(exponent) = data.exponents.iter().find(|exponent| **exponent > MAX_EXPONENT) {
+            return Err(BoardError::InvalidTile(format!("2^{}", exponent)));
+        }
+        Board::from_values(data.rows, data.cols, data.exponents.into_iter().map(exponent_to_value).collect())
+    }
+}
+
+/// Check whether the given value is a valid tile, which is 0 or a power of two from 2
+pub(crate) fn is_tile_value(value: u32) -> bool {
+    value != 1 && exponent_to_value(value_to_exponent(value)) == value
+}
+
+/// Parse a row of tile values separated by `,`
+fn parse_value_row(row: &str) -> Result<Vec<u32>, BoardError> {
+    row.strip_suffix(',').unwrap_or(row).split(',')
+        .map(|tile| match tile.trim().parse() {
+            Ok(value) if is_tile_value(value) => Ok(value),
+            _ => Err(BoardError::InvalidTile(tile.to_string())),
+        })
+        .collect()
+}
+
+/// Parse a row of tile exponents as hex digits
+fn parse_hex_row(row: &str) -> Result<Vec<u32>, BoardError> {
+    if row.is_empty() {
+        return Err(BoardError::Empty);
+    }
+    row.chars()
+        .map(|tile| match tile.to_digit(16) {
+            Some(exponent) => Ok(exponent_to_value(exponent as u8)),
+            None => Err(BoardError::InvalidTile(tile.to_string())),
+        })
+        .collect()
+}
+
 // No need to impl since move up/dn can be achieved by using two extra in-place transpose actions 
 // fn move_row_up(row: &Vec<u16>) -> Vec<u16> {
 //     unimplemented!()
@@ -41,88 +314,142 @@ impl Display for Board {
 //     unimplemented!()
 // }
 
-/// Moving single row toward left 
-fn move_row_left(row: &Vec<u16>) -> Vec<u16> {
-    // TODO: use reverse iterator to avoid extra one clone and two reverse opeartions 
-    let mut row_rev = row.clone();
-    row_rev.reverse();
-    let mut result = move_row_right(&row_rev);
-    result.reverse();
-    result
-}
-
-/// Moving single row toward right
-fn move_row_right(row: &Vec<u16>) -> Vec<u16> {
-    let mut pre_value = std::u16::MAX; 
-    let mut new_row = Vec::with_capacity(row.len());
-    let mut pre_idx = std::usize::MAX;
-    let row_len = row.len();
-    for (idx, val) in row.into_iter().enumerate() {
-        let mut tmp_val = *val;
-        if *val == 0 {
-        } else {
-           if pre_value == std::u16::MAX {
-               // set first element
-               pre_value = *val;
-               pre_idx = idx;
-               
-           } else {
-               // merge
-               if *val == pre_value {
-                  new_row[pre_idx] = 0; 
-                  pre_idx = std::usize::MAX;
-                  pre_value = std::u16::MAX;
-                  tmp_val = *val * 2;
-               } else {
-                   // set first element 
-                   pre_value = *val;
-                   pre_idx = idx;
-               }
-           }
-        }
-        new_row.push(tmp_val);
-    }
-
-    // move
-    let mut l = 0;
-    while l < row_len {
-        // only need to swap the zero from right to left
-        if new_row[l] == 0 {
-            // found non-zero and swap 
-            let mut idx = l;
-            let mut cur = l;
-            while idx > 0  {
-                idx -= 1;
-                if new_row[idx] > 0 {
-                    new_row.swap(cur, idx);
-                    cur = idx;
-                } else {
-                    break;
-                }
+/// The largest exponent of a tile that the value-level API can represent, which is the tile 2^31
+pub const MAX_EXPONENT: u8 = 31;
+
+/// Get the tile value of the given exponent, 0 means an empty tile
+pub fn exponent_to_value(exponent: u8) -> u32 {
+    if exponent == 0 {0} else {1 << exponent.min(MAX_EXPONENT)}
+}
+
+/// Get the exponent of the given tile value, 0 means an empty tile
+pub fn value_to_exponent(value: u32) -> u8 {
+    if value == 0 {0} else {(31 - value.leading_zeros()) as u8}
+}
+
+/// Slide and merge the given tiles (exponents) toward the first tile, which is next to the wall.
+/// The merge starts from the wall, and a merged tile can not be merged again in the same
+/// movement, e.g. `[2,2,2,0]` becomes `[4,2,0,0]`, and `[2,2,2,2]` becomes `[4,4,0,0]`.
+fn slide_tiles<'a>(tiles: impl Iterator<Item = &'a u8>, len: usize) -> (Vec<u8>, MoveInfo) {
+    let mut new_row: Vec<u8> = Vec::with_capacity(len);
+    let mut info = MoveInfo::default();
+    let mut mergeable = false;
+    for val in tiles.copied().filter(|val| *val != 0) {
+        match new_row.last_mut() {
+            Some(last) if mergeable && *last == val => {
+                *last = val + 1;
+                info.merges += 1;
+                info.score += exponent_to_value(*last);
+                mergeable = false;
+            },
+            _ => {
+                new_row.push(val);
+                mergeable = true;
             }
         }
-        l += 1;
     }
-    
-    new_row 
+    new_row.resize(len, 0);
+    (new_row, info)
 }
 
+/// Moving single row toward left, and return the new row with the outcome of the movement
+pub(crate) fn move_row_left(row: &[u8]) -> (Vec<u8>, MoveInfo) {
+    let (new_row, mut info) = slide_tiles(row.iter(), row.len());
+    info.moved = new_row.as_slice() != row;
+    (new_row, info)
+}
+
+/// Moving single row toward right, and return the new row with the outcome of the movement
+pub(crate) fn move_row_right(row: &[u8]) -> (Vec<u8>, MoveInfo) {
+    let (mut new_row, mut info) = slide_tiles(row.iter().rev(), row.len());
+    new_row.reverse();
+    info.moved = new_row.as_slice() != row;
+    (new_row, info)
+}
+
+
+/// A movement of a single row toward left or right
+type RowMove = fn(&[u8]) -> (Vec<u8>, MoveInfo);
+
+/// Get the size of a square board by the number of tiles
+fn square_size(len: usize) -> usize {
+    (len as f64).sqrt().round() as usize
+}
 
 impl Board {
-    /// Create a new `Board` object with the given tile values
+    /// Create a new square `Board` object with the given tile values, e.g. 16 values for
+    /// a 4x4 board or 25 values for a 5x5 board. Use `Board::try_from` to handle invalid values.
+    ///
+    /// # Arguments
+    /// * `values` Tile values row by row
+    ///
+    /// # Panics
+    /// Panics if the values can not make a square board, or any value is not a tile
+    ///
+    pub fn new(values: Vec<u32>) -> Self {
+        match Self::try_from(values) {
+            Ok(board) => board,
+            Err(err) => panic!("Invalid board: {}", err),
+        }
+    }
+
+    /// Create a new `Board` object with the given number of rows and columns, and tile values.
+    /// Use `Board::from_values` to handle invalid values.
     ///
     /// # Arguments
-    /// * `values` 16 tile values
+    /// * `rows` The number of rows
+    /// * `cols` The number of columns
+    /// * `values` `rows * cols` tile values row by row
     ///
-    pub fn new(val: Vec<u16>) -> Self {
-        Board { 
-            values: val,
-            rng: rand::thread_rng(),
+    /// # Panics
+    /// Panics if the number of values is not `rows * cols`, or any value is not a tile
+    ///
+    pub fn with_size(rows: usize, cols: usize, values: Vec<u32>) -> Self {
+        match Self::from_values(rows, cols, values) {
+            Ok(board) => board,
+            Err(err) => panic!("Invalid board: {}", err),
         }
     }
 
+    /// Create a new `Board` object with the given number of rows and columns, and tile values.
+    /// Returns an error if the number of values is not `rows * cols`, or any value is not 0 or
+    /// a power of two from 2.
+    ///
+    /// # Arguments
+    /// * `rows` The number of rows
+    /// * `cols` The number of columns
+    /// * `values` `rows * cols` tile values row by row
+    ///
+    pub fn from_values(rows: usize, cols: usize, values: Vec<u32>) -> Result<Self, BoardError> {
+        if rows == 0 || cols == 0 {
+            return Err(BoardError::Empty);
+        }
+        let len = rows.checked_mul(cols).ok_or(BoardError::TooLarge { rows, cols })?;
+        if values.len() != len {
+            return Err(BoardError::TileCount { expected: len, found: values.len() });
+        }
+        if let Some(value) = values.iter().find(|value| !is_tile_value(**value)) {
+            return Err(BoardError::InvalidTile(value.to_string()));
+        }
+        Ok(Board {
+            exponents: values.into_iter().map(value_to_exponent).collect(),
+            rows,
+            cols,
+        })
+    }
+
+    /// Get the number of rows
+    pub fn get_rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Get the number of columns
+    pub fn get_cols(&self) -> usize {
+        self.cols
+    }
+
     /// Change the tile color based on the tile value
-    fn get_color(&self, val: u16) -> &str {
+    fn get_color(&self, val: u32) -> &str {
         match val {
             1..=4      => "\x1b[31;1m",
             8..=32     => "\x1b[33;1m",
@@ -140,111 +467,283 @@ impl Board {
     }
 
     /// Return rows of the board 
-    fn rows(&self) -> [Vec<u16>; 4] {
-        let mut row1: Vec<u16> = vec![0;4];
-        let mut row2: Vec<u16> = vec![0;4];
-        let mut row3: Vec<u16> = vec![0;4];
-        let mut row4: Vec<u16> = vec![0;4];
-        row1.copy_from_slice(&self.values[0..4]);
-        row2.copy_from_slice(&self.values[4..8]);
-        row3.copy_from_slice(&self.values[8..12]);
-        row4.copy_from_slice(&self.values[12..16]);
-        [row1, row2, row3, row4]
+    fn split_rows(&self) -> Vec<Vec<u8>> {
+        self.exponents.chunks(self.cols).map(|row| row.to_vec()).collect()
     }
 
     /// Get tile values
-    pub fn get_values(&self) -> &Vec<u16> {
-        &self.values
+    pub fn get_values(&self) -> Vec<u32> {
+        self.exponents.iter().copied().map(exponent_to_value).collect()
     }
 
-    /// Move the all tiles in the horizontal direction
-    fn move_horizontal(&mut self, dir: Direction) {
+    /// Get tile values as exponents, e.g. 11 for the tile 2048 and 0 for an empty tile
+    pub fn get_exponents(&self) -> &[u8] {
+        &self.exponents
+    }
+
+    /// Move the all tiles in the horizontal direction by the given row movement, and return
+    /// the outcome of the movement
+    fn move_horizontal(&mut self, move_row: RowMove) -> MoveInfo {
         let mut start = 0;
-        let mut end = 4;
-        for row in self.rows().iter() {
-             match dir {
-               Direction::Left => {self.values.splice(start..end, move_row_left(row));}, 
-               Direction::Right => {self.values.splice(start..end, move_row_right(row));}, 
-               _ => panic!("Not support direction")
-             }
-             start += 4; 
-             end += 4;
+        let mut end = self.cols;
+        let mut info = MoveInfo::default();
+        for row in self.split_rows().iter() {
+             let (new_row, row_info) = move_row(row);
+             self.exponents.splice(start..end, new_row);
+             info += row_info;
+             start += self.cols; 
+             end += self.cols;
         }
+        info
+    }
+
+    /// Transpose the current tiles, which also swaps the number of rows and columns
+    pub fn transpose(&mut self) {
+        let mut scratch = vec![0; self.rows.max(self.cols)];
+        transpose::transpose_inplace(&mut self.exponents, &mut scratch, self.cols, self.rows);
+        std::mem::swap(&mut self.rows, &mut self.cols);
+    }
+
+    /// Reflect the current tiles left to right
+    pub fn reflect_horizontal(&mut self) {
+        for row in self.exponents.chunks_mut(self.cols) {
+            row.reverse();
+        }
+    }
+
+    /// Reflect the current tiles top to bottom
+    pub fn reflect_vertical(&mut self) {
+        self.exponents = self.exponents.chunks(self.cols).rev().flatten().copied().collect();
+    }
+
+    /// Rotate the current tiles clockwise by a quarter turn, which also swaps the number of
+    /// rows and columns
+    pub fn rotate_cw(&mut self) {
+        self.transpose();
+        self.reflect_horizontal();
+    }
+
+    /// Rotate the current tiles counterclockwise by a quarter turn, which also swaps the number
+    /// of rows and columns
+    pub fn rotate_ccw(&mut self) {
+        self.transpose();
+        self.reflect_vertical();
     }
 
-    /// Transpose the current tiles  
-    fn transpose(&mut self) {
-        let mut scratch = vec![0; 4];
-        transpose::transpose_inplace(&mut self.values, &mut scratch, 4, 4);
+    /// Get the canonical form of the board, which is the smallest board among its 8
+    /// rotations and reflections, with the transform from this board to the canonical one.
+    /// Boards that are equivalent by symmetry have the same canonical form.
+    pub fn canonical(&self) -> (Board, Transform) {
+        Transform::ALL.iter()
+            .map(|transform| (transform.apply(self), *transform))
+            .min_by(|(a, _), (b, _)| {
+                (a.rows, a.cols, &a.exponents).cmp(&(b.rows, b.cols, &b.exponents))
+            })
+            .unwrap()
     }
 
-    /// Move the all tiles in the vertical direction
-    fn move_vertical(&mut self, dir: Direction) {
+    /// Move the all tiles in the vertical direction by the given row movement on the
+    /// transposed tiles, and return the outcome of the movement
+    fn move_vertical(&mut self, move_row: RowMove) -> MoveInfo {
         // TODO: we use two extra transpose actions that allow all directions use the same logics. But it's not optimal process
         self.transpose();
-        match dir {
-            Direction::Up => self.move_horizontal(Direction::Left),
-            Direction::Down=> self.move_horizontal(Direction::Right),
-            _ => panic!("Not Supported"),
-        }
+        let info = self.move_horizontal(move_row);
         self.transpose();
+        info
     }
 
-    /// Move up all tiles 
-    pub fn move_up(&mut self) {
-        self.move_vertical(Direction::Up);
+    /// Move up all tiles, and return the outcome of the movement
+    pub fn move_up(&mut self) -> MoveInfo {
+        self.move_tiles(Direction::Up)
     }
 
-    /// Move down all tiles 
-    pub fn move_down(&mut self) {
-        self.move_vertical(Direction::Down);
+    /// Move down all tiles, and return the outcome of the movement
+    pub fn move_down(&mut self) -> MoveInfo {
+        self.move_tiles(Direction::Down)
     }
 
-    /// Move left all tiles 
-    pub fn move_left(&mut self) {
-        self.move_horizontal(Direction::Left);
+    /// Move left all tiles, and return the outcome of the movement
+    pub fn move_left(&mut self) -> MoveInfo {
+        self.move_tiles(Direction::Left)
     }
 
-    /// Move right all tiles 
-    pub fn move_right(&mut self) {
-        self.move_horizontal(Direction::Right);
+    /// Move right all tiles, and return the outcome of the movement
+    pub fn move_right(&mut self) -> MoveInfo {
+        self.move_tiles(Direction::Right)
     }
 
-    /// Set a single tile by the given value
-    fn set_tile_value(&mut self, index: u8, value: u16) {
-        self.values[index as usize] = value;
+    /// Move all tiles toward the given direction, and return the outcome of the movement.
+    /// A 4x4 board is moved by the lookup tables of `BitBoard` unless it has a 32768 tile,
+    /// which can not be merged in 4 bits, and other boards are moved row by row.
+    ///
+    /// # Arguments
+    /// * `dir` The direction of tile movement
+    ///
+    pub fn move_tiles(&mut self, dir: Direction) -> MoveInfo {
+        match self.move_packed(dir) {
+            Some(info) => info,
+            None => self.move_unpacked(dir),
+        }
     }
 
-    /// Find an empty tile and set the tile by a random value
-    pub fn generate_new_tile(&mut self) -> bool {
-        // get all zero tile 
-        let mut zero_list = Vec::new();
-        for (idx, val) in self.values.iter().enumerate() {
-            if *val == 0 {
-                zero_list.push(idx);
-            }
+    /// Move all tiles by `BitBoard`, returns `None` if the board can not be moved exactly by it
+    fn move_packed(&mut self, dir: Direction) -> Option<MoveInfo> {
+        if self.exponents.iter().any(|exponent| *exponent >= MAX_BIT_EXPONENT) {
+            return None;
         }
+        let mut packed = BitBoard::from_board(self)?;
+        let info = packed.move_tiles(dir);
+        for (idx, exponent) in self.exponents.iter_mut().enumerate() {
+            *exponent = packed.get_exponent(idx);
+        }
+        Some(info)
+    }
+
+    /// Move all tiles row by row, which supports any size and tile
+    fn move_unpacked(&mut self, dir: Direction) -> MoveInfo {
+        match dir {
+            Direction::Up => self.move_vertical(move_row_left),
+            Direction::Down => self.move_vertical(move_row_right),
+            Direction::Left => self.move_horizontal(move_row_left),
+            Direction::Right => self.move_horizontal(move_row_right),
+        }
+    }
 
-        if zero_list.len() > 0 {
-            // choose zero cell 
-            let rand_idx: usize = self.rng.gen::<usize>() % zero_list.len();
-            // choose value 2 or 4
-            let r: f32  = self.rng.gen();
-            let rand_val = if r < 0.2 {4} else {2};
-            self.set_tile_value(zero_list[rand_idx] as u8, rand_val);
-            true
+    /// Get a new board with all tiles moved toward the given direction, and the outcome of the
+    /// movement. The current board is not changed. Returns `None` if no tile is moved.
+    ///
+    /// # Arguments
+    /// * `dir` The direction of tile movement
+    ///
+    pub fn apply(&self, dir: Direction) -> Option<(Board, MoveInfo)> {
+        let mut board = self.clone();
+        let info = board.move_tiles(dir);
+        if info.moved {
+            Some((board, info))
         } else {
-            false
+            None
+        }
+    }
+
+    /// Get the directions that move any tile, in the order of left, right, up and down
+    pub fn legal_moves(&self) -> Vec<Direction> {
+        Direction::ALL.iter()
+            .copied()
+            .filter(|dir| self.apply(*dir).is_some())
+            .collect()
+    }
+
+    /// Get a new board with a single tile set by the given value. The current board is not
+    /// changed. Returns `BoardError` if the index is out of the board or the value is not a tile.
+    ///
+    /// # Arguments
+    /// * `index` The index of the tile row by row
+    /// * `value` The tile value, 0 means an empty tile
+    ///
+    pub fn with_tile(&self, index: usize, value: u32) -> Result<Board, BoardError> {
+        if index >= self.exponents.len() {
+            return Err(BoardError::InvalidIndex { index, len: self.exponents.len() });
+        }
+        if !is_tile_value(value) {
+            return Err(BoardError::InvalidTile(value.to_string()));
+        }
+        Ok(self.with_tile_value(index, value))
+    }
+
+    /// Get a new board with a single tile set by the given value, which are checked by the caller
+    pub(crate) fn with_tile_value(&self, index: usize, value: u32) -> Board {
+        let mut board = self.clone();
+        board.set_tile_value(index, value);
+        board
+    }
+
+    /// Set a single tile by the given value, which are checked by the caller
+    pub(crate) fn set_tile_value(&mut self, index: usize, value: u32) {
+        debug_assert!(is_tile_value(value), "invalid tile {}", value);
+        self.exponents[index] = value_to_exponent(value);
+    }
+
+    /// Get the indexes of all empty tiles
+    pub fn empty_tiles(&self) -> Vec<usize> {
+        self.exponents.iter().enumerate()
+            .filter(|(_, val)| **val == 0)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Find an empty tile and set the tile by a random value by the classic rule, see `ClassicSpawn`
+    ///
+    /// # Arguments
+    /// * `rng` The random generator
+    ///
+    pub fn generate_new_tile(&mut self, rng: &mut dyn RngCore) -> bool {
+        self.generate_new_tile_by(&mut ClassicSpawn, rng)
+    }
+
+    /// Generate new tiles by the given spawn policy and random generator. If return values is
+    /// false, it means no tile is generated.
+    ///
+    /// # Arguments
+    /// * `policy` The spawn policy that decides where and which tiles are generated
+    /// * `rng` The random generator
+    ///
+    pub fn generate_new_tile_by<P: SpawnPolicy + ?Sized>(&mut self, policy: &mut P, rng: &mut dyn RngCore) -> bool {
+        policy.spawn(self, rng)
+    }
+
+    /// Get the largest tile value on the board
+    pub fn max_tile(&self) -> u32 {
+        exponent_to_value(self.exponents.iter().copied().max().unwrap_or(0))
+    }
+
+    /// Check whether any movement is still possible, which means there is an empty tile
+    /// or two neighboring tiles with the same value
+    pub fn can_move(&self) -> bool {
+        if self.exponents.contains(&0) {
+            return true;
         }
+        self.exponents.chunks(self.cols).any(|row| row.windows(2).any(|w| w[0] == w[1]))
+            || (0..self.exponents.len() - self.cols)
+                .any(|idx| self.exponents[idx] == self.exponents[idx + self.cols])
     }
 
     /// Reset all tile values to 0
     pub fn reset(&mut self) {
-        for val in self.values.iter_mut() {
+        for val in self.exponents.iter_mut() {
             *val = 0;
         }
     }
 
+    /// Get the text of a tile that fits in a cell. Tiles up to 6 digits are shown as their
+    /// values, and larger tiles are shown as powers of two, e.g. `2^20` for 1048576.
+    fn tile_text(exponent: u8) -> String {
+        match exponent {
+            0 => String::new(),
+            1..=19 => exponent_to_value(exponent).to_string(),
+            _ => format!("2^{}", exponent),
+        }
+    }
+
+    /// Draw a horizontal border line of the board by the given characters
+    fn border_line(&self, left: char, middle: char, right: char) -> String {
+        let cells = vec!["═══════"; self.cols];
+        format!("{}{}{}", left, cells.join(&middle.to_string()), right)
+    }
+
+    /// Used for the alternate form of Display trait, that returns the compact notation
+    fn write_notation(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let rows: Vec<String> = self.exponents.chunks(self.cols)
+            .map(|row| {
+                let values: Vec<String> = row.iter()
+                    .map(|exponent| exponent_to_value(*exponent).to_string())
+                    .collect();
+                // a single column is written as `2,`, which is not an exponent
+                if values.len() == 1 {values[0].clone() + ","} else {values.join(",")}
+            })
+            .collect();
+        write!(f, "{}", rows.join("/"))
+    }
+
     /// Used for Display trait, that returns a drawn board 
     pub fn draw(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         let mut texts = String::new();
@@ -252,41 +751,31 @@ impl Board {
         let padding = "   ";
 
         // start  
-        texts.push_str( &format!("{p}╔═══════╦═══════╦═══════╦═══════╗{n}",
-                p = padding, n = newline));
+        texts.push_str( &format!("{p}{b}{n}",
+                p = padding, b = self.border_line('╔', '╦', '╗'), n = newline));
         // content
-        for (i, value) in self.values.iter().enumerate() {
-             let val_str = (*value).to_string();
-             match i {
-                  i if i % 4 == 0 => {
-                        texts.push_str( &format!("{p}║{c}{v:^7}{r}", 
-                                p = padding, 
-                                c = self.get_color(*value), 
-                                v = if *value > 0 { &val_str } else {" "}, 
-                                r = self.get_color_reset()))
-                  }, 
-                  i if i % 4 == 3 => { 
-                    texts.push_str( &format!("║{c}{v:^7}{r}║{n}", 
-                            c = self.get_color(*value), 
-                            v = if *value > 0 { &val_str } else {" "}, 
-                            r = self.get_color_reset(),
-                            n = newline));
-                    if i != 15 {
-                      texts.push_str( &format!("{p}╠═══════╬═══════╬═══════╬═══════╣{n}", 
-                              p = padding, n = newline));
-                    }
-                  },
-                  _ =>  {
-                    texts.push_str( &format!("║{c}{v:^7}{r}", 
-                            c = self.get_color(*value), 
-                            v = if *value > 0 { &val_str } else {" "}, 
-                            r = self.get_color_reset()));
-                  }
+        for (i, exponent) in self.exponents.iter().enumerate() {
+             let value = exponent_to_value(*exponent);
+             let val_str = Self::tile_text(*exponent);
+             let col = i % self.cols;
+             if col == 0 {
+                 texts.push_str(padding);
+             }
+             texts.push_str( &format!("║{c}{v:^7}{r}", 
+                     c = self.get_color(value), 
+                     v = val_str, 
+                     r = self.get_color_reset()));
+             if col == self.cols - 1 {
+                 texts.push_str( &format!("║{n}", n = newline));
+                 if i != self.exponents.len() - 1 {
+                     texts.push_str( &format!("{p}{b}{n}", 
+                             p = padding, b = self.border_line('╠', '╬', '╣'), n = newline));
+                 }
              }
         }
         // end 
-        texts.push_str( &format!("{p}╚═══════╩═══════╩═══════╩═══════╝{n}",
-                p = padding, n = newline));
+        texts.push_str( &format!("{p}{b}{n}",
+                p = padding, b = self.border_line('╚', '╩', '╝'), n = newline));
 
         write!(f, "{}", texts)
     }
@@ -296,6 +785,8 @@ impl Board {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
 
     #[test]
     fn test_board_draw() {
@@ -316,6 +807,173 @@ mod tests {
         }
         let board = Board::new(values);
         println!("{}", board);
+        let text = board.to_string();
+        assert_eq!(text.matches('\n').count(), 9);
+        assert!(text.contains("╔═══════╦═══════╦═══════╦═══════╗"));
+        assert!(text.contains(" 16384 ") && text.contains(" 32768 "));
+    }
+
+    #[test]
+    fn test_board_draw_large_tiles() {
+        let values = vec![
+                65536, 131072, 262144, 524288,
+                1 << 20, 1 << 24, 1 << 31, 0,
+            ];
+        let board = Board::with_size(2, 4, values);
+        let text = board.to_string();
+        assert!(text.contains(" 65536 "));
+        assert!(text.contains("131072"));
+        assert!(text.contains("524288"));
+        assert!(text.contains(" 2^20  "));
+        assert!(text.contains(" 2^24  "));
+        assert!(text.contains(" 2^31  "));
+
+        // every cell keeps the 7 characters layout
+        let width = |line: &str| {
+            let mut plain = String::new();
+            let mut in_escape = false;
+            for c in line.chars() {
+                match c {
+                    '\r' => {},
+                    '\x1b' => in_escape = true,
+                    'm' if in_escape => in_escape = false,
+                    _ if !in_escape => plain.push(c),
+                    _ => {}
+                }
+            }
+            plain.trim_end().chars().count()
+        };
+        let widths: Vec<usize> = text.split('\n').filter(|line| !line.trim().is_empty())
+            .map(width).collect();
+        assert!(widths.iter().all(|w| *w == widths[0]));
+    }
+
+    #[test]
+    fn test_board_merge_large_tiles() {
+        let values = vec![
+                32768, 32768, 0, 0,
+                65536, 65536, 0, 0,
+                0, 0, 0, 0,
+                0, 0, 0, 0,
+            ];
+        let mut board = Board::new(values);
+        let info = board.move_left();
+        assert_eq!(info.score, 65536 + 131072);
+        assert_eq!(board.get_values()[0], 65536);
+        assert_eq!(board.get_values()[4], 131072);
+        assert_eq!(board.get_exponents()[4], 17);
+        assert_eq!(board.max_tile(), 131072);
+    }
+
+    #[test]
+    fn test_board_move_packed() {
+        use rand::Rng;
+
+        // 4x4 boards moved by `BitBoard` are the same as moved row by row, and boards with
+        // 32768 tiles fall back to the row movements
+        let mut rng = ChaCha8Rng::seed_from_u64(4096);
+        for _ in 0..5000 {
+            let values: Vec<u32> = (0..16)
+                .map(|_| if rng.gen_bool(0.3) {0} else {1 << rng.gen_range(1..=16)})
+                .collect();
+            let origin = Board::new(values);
+            for dir in Direction::ALL.iter() {
+                let mut packed = origin.clone();
+                let mut unpacked = origin.clone();
+                assert_eq!(packed.move_tiles(*dir), unpacked.move_unpacked(*dir));
+                assert_eq!(packed, unpacked);
+            }
+        }
+        let mut board: Board = "32768,32768,0,0/0,0,0,0/0,0,0,0/0,0,0,0".parse().unwrap();
+        let mut packed = board.clone();
+        assert_eq!(packed.move_packed(Direction::Left), None);
+        assert_eq!(board.move_left().score, 65536);
+        let mut board: Board = "2,2,0/0,0,0/0,0,0".parse().unwrap();
+        assert_eq!(board.move_packed(Direction::Left), None);
+    }
+
+    #[test]
+    fn test_exponent_value() {
+        assert_eq!(value_to_exponent(0), 0);
+        assert_eq!(value_to_exponent(2), 1);
+        assert_eq!(value_to_exponent(2048), 11);
+        assert_eq!(value_to_exponent(1 << 31), 31);
+        assert_eq!(exponent_to_value(0), 0);
+        assert_eq!(exponent_to_value(1), 2);
+        assert_eq!(exponent_to_value(17), 131072);
+    }
+
+    #[test]
+    fn test_board_draw_size() {
+        let board = Board::with_size(2, 3, vec![2, 0, 4, 8, 16, 0]);
+        let text = board.to_string();
+        assert_eq!(text.matches('\n').count(), 5);
+        assert!(text.contains("╔═══════╦═══════╦═══════╗"));
+        assert!(text.contains("╠═══════╬═══════╬═══════╣"));
+        assert!(text.contains("╚═══════╩═══════╩═══════╝"));
+    }
+
+    #[test]
+    fn test_board_size() {
+        let board = Board::new(vec![0; 9]);
+        assert_eq!((board.get_rows(), board.get_cols()), (3, 3));
+        let board: Board = Default::default();
+        assert_eq!((board.get_rows(), board.get_cols()), (4, 4));
+        let board = Board::with_size(2, 5, vec![0; 10]);
+        assert_eq!((board.get_rows(), board.get_cols()), (2, 5));
+    }
+
+    #[test]
+    fn test_board_move_rectangular() {
+        let values = vec![
+                2, 2, 0, 4, 4,
+                0, 2, 8, 0, 8,
+                2, 0, 8, 4, 0,
+            ];
+        let mut board = Board::with_size(3, 5, values.clone());
+        let info = board.move_left();
+        assert_eq!(info, MoveInfo { moved: true, merges: 3, score: 28 });
+        assert_eq!(board.get_values().to_vec(), vec![
+                4, 8, 0, 0, 0,
+                2, 16, 0, 0, 0,
+                2, 8, 4, 0, 0,
+            ]);
+
+        let mut board = Board::with_size(3, 5, values.clone());
+        board.move_right();
+        assert_eq!(board.get_values().to_vec(), vec![
+                0, 0, 0, 4, 8,
+                0, 0, 0, 2, 16,
+                0, 0, 2, 8, 4,
+            ]);
+
+        let mut board = Board::with_size(3, 5, values.clone());
+        let info = board.move_up();
+        assert_eq!(info, MoveInfo { moved: true, merges: 4, score: 32 });
+        assert_eq!((board.get_rows(), board.get_cols()), (3, 5));
+        assert_eq!(board.get_values().to_vec(), vec![
+                4, 4, 16, 8, 4,
+                0, 0, 0, 0, 8,
+                0, 0, 0, 0, 0,
+            ]);
+
+        let mut board = Board::with_size(3, 5, values);
+        board.move_down();
+        assert_eq!(board.get_values().to_vec(), vec![
+                0, 0, 0, 0, 0,
+                0, 0, 0, 0, 4,
+                4, 4, 16, 8, 8,
+            ]);
+    }
+
+    #[test]
+    fn test_board_can_move_rectangular() {
+        let board = Board::with_size(2, 3, vec![2, 4, 2, 4, 2, 4]);
+        assert!(!board.can_move());
+        let board = Board::with_size(2, 3, vec![2, 4, 2, 4, 2, 2]);
+        assert!(board.can_move());
+        let board = Board::with_size(3, 2, vec![2, 4, 8, 16, 8, 2]);
+        assert!(board.can_move());
     }
 
     #[test]
@@ -346,7 +1004,8 @@ mod tests {
             ];
         let mut board = Board::new(values);
         // println!("{}", board);
-        board.move_right();
+        let info = board.move_right();
+        assert_eq!(info, MoveInfo { moved: true, merges: 1, score: 8 });
         assert_eq!(board.get_values().to_vec(), expected_values);
         // let new_board = Board::new(board.get_values());
         // println!("{}", new_board);
@@ -390,7 +1049,8 @@ mod tests {
             ];
         let mut board = Board::new(values);
         // println!("{}", board);
-        board.move_up();
+        let info = board.move_up();
+        assert_eq!(info, MoveInfo { moved: true, merges: 3, score: 100 });
         assert_eq!(board.get_values().to_vec(), expected_values);
         // let new_board = Board::new(board.get_values().to_vec());
         // println!("{}", new_board);
@@ -418,6 +1078,45 @@ mod tests {
         // println!("{}", new_board);
     }
 
+    #[test]
+    fn test_board_merge_priority() {
+        let values = vec![
+                2, 2, 2, 0,
+                2, 0, 0, 0,
+                2, 0, 0, 0,
+                0, 0, 0, 0,
+            ];
+        let mut board = Board::new(values.clone());
+        board.move_right();
+        assert_eq!(board.get_values()[0..4].to_vec(), vec![0, 0, 2, 4]);
+        let mut board = Board::new(values.clone());
+        board.move_left();
+        assert_eq!(board.get_values()[0..4].to_vec(), vec![4, 2, 0, 0]);
+        let mut board = Board::new(values.clone());
+        board.move_down();
+        let column: Vec<u32> = board.get_values().iter().step_by(4).copied().collect();
+        assert_eq!(column, vec![0, 0, 2, 4]);
+        let mut board = Board::new(values);
+        board.move_up();
+        let column: Vec<u32> = board.get_values().iter().step_by(4).copied().collect();
+        assert_eq!(column, vec![4, 2, 0, 0]);
+    }
+
+    #[test]
+    fn test_board_move_not_moved() {
+        let values = vec![
+                2, 4, 0, 0,
+                8, 0, 0, 0,
+                0, 0, 0, 0,
+                0, 0, 0, 0,
+            ];
+        let mut board = Board::new(values.clone());
+        assert_eq!(board.move_left(), MoveInfo::default());
+        assert_eq!(board.move_up(), MoveInfo::default());
+        assert_eq!(board.get_values().to_vec(), values);
+        assert!(board.move_right().moved);
+    }
+
     #[test]
     fn test_board_transpose() {
         let values = vec![
@@ -462,6 +1161,7 @@ mod tests {
 
     #[test]
     fn test_board_generate_new_tile() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
         let values = vec![
                 0, 2, 512, 16,
                 0, 2, 8, 16,
@@ -469,8 +1169,8 @@ mod tests {
                 2, 0, 4, 32,
             ];
         let mut board = Board::new(values);
-        let r = board.generate_new_tile();
-        assert_eq!(r, true);
+        let r = board.generate_new_tile(&mut rng);
+        assert!(r);
 
         let values = vec![
                 2, 2, 512, 16,
@@ -479,8 +1179,217 @@ mod tests {
                 2, 4, 4, 32,
             ];
         let mut board = Board::new(values);
-        let r = board.generate_new_tile();
-        assert_eq!(r, false);
+        let r = board.generate_new_tile(&mut rng);
+        assert!(!r);
+    }
+
+    #[test]
+    fn test_board_can_move() {
+        let values = vec![
+                2, 4, 2, 4,
+                4, 2, 4, 2,
+                2, 4, 2, 4,
+                4, 2, 4, 0,
+            ];
+        assert!(Board::new(values).can_move());
+
+        let values = vec![
+                2, 4, 2, 4,
+                4, 2, 4, 2,
+                2, 4, 2, 4,
+                4, 2, 4, 4,
+            ];
+        assert!(Board::new(values).can_move());
+
+        let values = vec![
+                2, 4, 2, 4,
+                4, 2, 4, 2,
+                2, 4, 8, 4,
+                4, 2, 8, 2,
+            ];
+        assert!(Board::new(values).can_move());
+
+        let values = vec![
+                2, 4, 2, 4,
+                4, 2, 4, 2,
+                2, 4, 2, 4,
+                4, 2, 4, 2,
+            ];
+        let board = Board::new(values);
+        assert!(!board.can_move());
+        assert_eq!(board.max_tile(), 4);
+    }
+
+    #[test]
+    fn test_board_seeded_rng() {
+        let mut rng1 = ChaCha8Rng::seed_from_u64(2048);
+        let mut rng2 = ChaCha8Rng::seed_from_u64(2048);
+        let mut board1 = Board::default();
+        let mut board2 = Board::default();
+        for _ in 0..8 {
+            assert!(board1.generate_new_tile(&mut rng1));
+            assert!(board2.generate_new_tile(&mut rng2));
+        }
+        assert_eq!(board1, board2);
+    }
+
+    #[test]
+    fn test_board_apply() {
+        let values = vec![
+                2, 2, 0, 0,
+                0, 0, 0, 0,
+                0, 0, 0, 0,
+                0, 0, 0, 0,
+            ];
+        let board = Board::new(values.clone());
+        let (left, info) = board.apply(Direction::Left).unwrap();
+        assert_eq!(info, MoveInfo { moved: true, merges: 1, score: 4 });
+        assert_eq!(left.get_values()[..4], [4, 0, 0, 0]);
+        // the origin board is not changed
+        assert_eq!(board.get_values(), values);
+        assert!(board.apply(Direction::Up).is_none());
+
+        assert_eq!(board.legal_moves(), vec![Direction::Left, Direction::Right, Direction::Down]);
+        let full = Board::new(vec![2, 4, 4, 2]);
+        assert!(full.legal_moves().is_empty());
+
+        let board = board.with_tile(15, 8).unwrap();
+        assert_eq!(board.get_values()[15], 8);
+        assert_eq!(board.legal_moves().len(), 4);
+        assert_eq!(board.with_tile(0, 0).unwrap().get_values()[0], 0);
+        assert_eq!(board.get_values()[0], 2);
+        assert_eq!(board.with_tile(0, 3), Err(BoardError::InvalidTile("3".to_string())));
+        assert_eq!(board.with_tile(0, 1), Err(BoardError::InvalidTile("1".to_string())));
+        let err = board.with_tile(16, 2).unwrap_err();
+        assert_eq!(err, BoardError::InvalidIndex { index: 16, len: 16 });
+        assert_eq!(err.to_string(), "tile index 16 is out of the board of 16 tiles");
+    }
+
+    #[test]
+    fn test_board_value_type() {
+        use std::collections::HashSet;
+
+        fn assert_send<T: Send>() {}
+        assert_send::<Board>();
+
+        let board = Board::new(vec![0, 2, 0, 4]);
+        let mut moved = board.clone();
+        moved.move_left();
+        assert_ne!(board, moved);
+        moved.move_right();
+        assert_eq!(board, moved);
+
+        let mut set = HashSet::new();
+        set.insert(board.clone());
+        assert!(set.contains(&moved));
+        assert!(!set.contains(&Board::new(vec![0, 0, 0, 4])));
+    }
+
+    #[test]
+    fn test_board_notation() {
+        let values = vec![
+                0, 2, 512, 16,
+                0, 2, 8, 16,
+                4, 4, 2, 32,
+                2, 0, 4, 32,
+            ];
+        let board = Board::new(values);
+        let notation = format!("{:#}", board);
+        assert_eq!(notation, "0,2,512,16/0,2,8,16/4,4,2,32/2,0,4,32");
+        assert_eq!(notation.parse::<Board>(), Ok(board.clone()));
+        assert_eq!("0194/0134/2215/1025".parse::<Board>(), Ok(board));
+
+        let board: Board = " 2, 0, 1048576 / 0, 4, 0 ".parse().unwrap();
+        assert_eq!((board.get_rows(), board.get_cols()), (2, 3));
+        assert_eq!(format!("{:#}", board), "2,0,1048576/0,4,0");
+        assert_eq!("k0/00".parse::<Board>(), Err(BoardError::InvalidTile("k".to_string())));
+        assert_eq!("2,3/0,0".parse::<Board>(), Err(BoardError::InvalidTile("3".to_string())));
+        assert_eq!("2,0/0".parse::<Board>(), Err(BoardError::RaggedRows));
+        assert_eq!("".parse::<Board>(), Err(BoardError::Empty));
+        assert_eq!("12//00".parse::<Board>(), Err(BoardError::Empty));
+        assert_eq!("2,,/4".parse::<Board>(), Err(BoardError::InvalidTile("".to_string())));
+    }
+
+    #[test]
+    fn test_board_notation_line() {
+        // boards of a single column or row are parsed back as values
+        for len in 1..=8 {
+            let values: Vec<u32> = (0..len).map(|idx| if idx % 3 == 1 {0} else {2 << idx}).collect();
+            for (rows, cols) in [(len, 1), (1, len)] {
+                let board = Board::with_size(rows, cols, values.clone());
+                let notation = format!("{:#}", board);
+                assert_eq!(notation.parse::<Board>(), Ok(board), "{}", notation);
+            }
+        }
+        assert_eq!(format!("{:#}", Board::with_size(3, 1, vec![2, 4, 16])), "2,/4,/16,");
+        assert_eq!(format!("{:#}", Board::with_size(1, 3, vec![2, 4, 16])), "2,4,16");
+        assert_eq!("2/4/8".parse::<Board>(), Ok(Board::with_size(3, 1, vec![4, 16, 256])));
+    }
+
+    #[test]
+    fn test_board_from_values() {
+        let board = Board::from_values(2, 3, vec![2, 0, 4, 8, 0, 2]).unwrap();
+        assert_eq!(board, Board::with_size(2, 3, vec![2, 0, 4, 8, 0, 2]));
+        assert_eq!(Board::from_values(2, 3, vec![0; 5]),
+            Err(BoardError::TileCount { expected: 6, found: 5 }));
+        assert_eq!(Board::from_values(0, 3, vec![]), Err(BoardError::Empty));
+        assert_eq!(Board::from_values(1, 2, vec![2, 3]), Err(BoardError::InvalidTile("3".to_string())));
+        assert_eq!(Board::from_values(1, 2, vec![1, 0]), Err(BoardError::InvalidTile("1".to_string())));
+        assert_eq!(Board::from_values(usize::MAX, 2, vec![]), Err(BoardError::TooLarge { rows: usize::MAX, cols: 2 }));
+
+        assert_eq!(Board::try_from(vec![0; 16]), Ok(Board::default()));
+        assert_eq!(Board::try_from(vec![0; 15]), Err(BoardError::NotSquare(15)));
+        assert_eq!(Board::try_from(vec![]), Err(BoardError::Empty));
+        assert_eq!(BoardError::NotSquare(15).to_string(), "15 tiles can not make a square board");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid board")]
+    fn test_board_new_invalid() {
+        Board::new(vec![2; 15]);
+    }
+
+    #[test]
+    fn test_direction() {
+        for (code, dir) in Direction::ALL.iter().enumerate() {
+            assert_eq!(*dir as usize, code);
+            assert_eq!(Direction::try_from(code as i32), Ok(*dir));
+            assert_eq!(dir.name().parse(), Ok(*dir));
+            assert_eq!(dir.opposite().opposite(), *dir);
+            assert_eq!(dir.rotate_cw().rotate_ccw(), *dir);
+            assert_eq!(dir.rotate_cw().rotate_cw(), dir.opposite());
+            assert_eq!(dir.reflect_horizontal().reflect_horizontal(), *dir);
+            assert_eq!(dir.reflect_vertical().reflect_vertical(), *dir);
+            assert_eq!(dir.transpose().transpose(), *dir);
+        }
+        assert_eq!(Direction::try_from(4), Err(DirectionError("4".to_string())));
+        assert!(Direction::try_from(-1).is_err());
+        assert_eq!("DOWN".parse(), Ok(Direction::Down));
+        assert_eq!("k".parse(), Ok(Direction::Up));
+        assert_eq!("a".parse(), Ok(Direction::Left));
+        assert_eq!("x".parse::<Direction>().unwrap_err().to_string(), "invalid direction `x`");
+        assert_eq!(Direction::Up.rotate_cw(), Direction::Right);
+        assert_eq!(Direction::Left.transpose(), Direction::Up);
+    }
+
+    #[test]
+    fn test_direction_transform_board() {
+        // moving the rotated board equals to rotating the board moved to the rotated direction
+        let values = vec![
+                2, 2, 0, 4,
+                0, 8, 8, 0,
+                4, 0, 2, 2,
+                0, 16, 0, 2,
+            ];
+        let board = Board::new(values);
+        let mut transposed = board.clone();
+        transposed.transpose();
+        for dir in Direction::ALL.iter() {
+            let (mut moved, _) = board.apply(*dir).unwrap();
+            moved.transpose();
+            let (expected, _) = transposed.apply(dir.transpose()).unwrap();
+            assert_eq!(moved, expected);
+        }
     }
 
     #[test]
@@ -496,41 +1405,82 @@ mod tests {
     }
 }
 
+/// Moving single row of tile values toward left (Only for testing)
+#[cfg(test)]
+fn move_values_left(row: &[u32]) -> (Vec<u32>, MoveInfo) {
+    let exponents: Vec<u8> = row.iter().copied().map(value_to_exponent).collect();
+    let (result, info) = move_row_left(&exponents);
+    (result.into_iter().map(exponent_to_value).collect(), info)
+}
+
+/// Moving single row of tile values toward right (Only for testing)
+#[cfg(test)]
+fn move_values_right(row: &[u32]) -> (Vec<u32>, MoveInfo) {
+    let exponents: Vec<u8> = row.iter().copied().map(value_to_exponent).collect();
+    let (result, info) = move_row_right(&exponents);
+    (result.into_iter().map(exponent_to_value).collect(), info)
+}
+
 #[test]
 fn test_row_move_left() {
     let test_case = vec![0,2,2,4];
     let expected = vec![4,4,0,0];
-    let result = move_row_left(&test_case);
+    let (result, _) = move_values_left(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![2,0,2,4];
     let expected = vec![4,4,0,0];
-    let result = move_row_left(&test_case);
+    let (result, _) = move_values_left(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![16,0,4,4];
     let expected = vec![16,8,0,0];
-    let result = move_row_left(&test_case);
+    let (result, _) = move_values_left(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![0,2,0,2];
     let expected = vec![4,0,0,0];
-    let result = move_row_left(&test_case);
+    let (result, _) = move_values_left(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![2,2,2,2];
     let expected = vec![4,4,0,0];
-    let result = move_row_left(&test_case);
+    let (result, _) = move_values_left(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![2,4,2,0];
     let expected = vec![2,4,2,0];
-    let result = move_row_left(&test_case);
+    let (result, _) = move_values_left(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![16,0,2,0];
     let expected = vec![16,2,0,0];
-    let result = move_row_left(&test_case);
+    let (result, _) = move_values_left(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![2,2,2,0];
+    let expected = vec![4,2,0,0];
+    let (result, _) = move_values_left(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![0,2,2,2];
+    let expected = vec![4,2,0,0];
+    let (result, _) = move_values_left(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![4,4,8,8];
+    let expected = vec![8,16,0,0];
+    let (result, _) = move_values_left(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![4,2,2,0];
+    let expected = vec![4,4,0,0];
+    let (result, _) = move_values_left(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![8,4,4,8];
+    let expected = vec![8,8,8,0];
+    let (result, _) = move_values_left(&test_case);
     assert_eq!(result, expected);
 }
 
@@ -538,36 +1488,137 @@ fn test_row_move_left() {
 fn test_row_move_right() {
     let test_case = vec![0,2,2,4];
     let expected = vec![0,0,4,4];
-    let result = move_row_right(&test_case);
+    let (result, _) = move_values_right(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![2,0,2,4];
     let expected = vec![0,0,4,4];
-    let result = move_row_right(&test_case);
+    let (result, _) = move_values_right(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![16,0,4,4];
     let expected = vec![0,0,16,8];
-    let result = move_row_right(&test_case);
+    let (result, _) = move_values_right(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![0,2,0,2];
     let expected = vec![0,0,0,4];
-    let result = move_row_right(&test_case);
+    let (result, _) = move_values_right(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![2,2,2,2];
     let expected = vec![0,0,4,4];
-    let result = move_row_right(&test_case);
+    let (result, _) = move_values_right(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![2,4,2,0];
     let expected = vec![0,2,4,2];
-    let result = move_row_right(&test_case);
+    let (result, _) = move_values_right(&test_case);
     assert_eq!(result, expected);
 
     let test_case = vec![16,0,2,0];
     let expected = vec![0,0,16,2];
-    let result = move_row_right(&test_case);
+    let (result, _) = move_values_right(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![2,2,2,0];
+    let expected = vec![0,0,2,4];
+    let (result, _) = move_values_right(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![0,2,2,2];
+    let expected = vec![0,0,2,4];
+    let (result, _) = move_values_right(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![4,4,8,8];
+    let expected = vec![0,0,8,16];
+    let (result, _) = move_values_right(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![4,4,4,4];
+    let expected = vec![0,0,8,8];
+    let (result, _) = move_values_right(&test_case);
+    assert_eq!(result, expected);
+
+    let test_case = vec![8,4,4,8];
+    let expected = vec![0,8,8,8];
+    let (result, _) = move_values_right(&test_case);
     assert_eq!(result, expected);
 }
+
+#[test]
+fn test_row_move_score() {
+    let test_cases = vec![
+        (vec![0,2,2,4], 4),
+        (vec![2,2,2,2], 8),
+        (vec![4,4,8,8], 24),
+        (vec![2,4,8,16], 0),
+        (vec![0,0,0,0], 0),
+    ];
+    for (row, expected) in test_cases {
+        assert_eq!(move_values_left(&row).1.score, expected);
+        assert_eq!(move_values_right(&row).1.score, expected);
+    }
+}
+
+#[test]
+fn test_row_move_moved() {
+    assert!(!move_values_left(&[2,4,8,0]).1.moved);
+    assert!(move_values_right(&[2,4,8,0]).1.moved);
+    assert!(!move_values_right(&[0,0,0,0]).1.moved);
+    assert!(move_values_right(&[0,0,2,2]).1.moved);
+}
+
+/// Reference implementation of moving a single row toward left by the classic three steps:
+/// compress, merge neighbors from the left, and compress again
+#[cfg(test)]
+fn reference_move_row_left(row: &[u32]) -> (Vec<u32>, u32) {
+    let mut tiles: Vec<u32> = row.iter().copied().filter(|val| *val != 0).collect();
+    let mut score = 0;
+    let mut i = 0;
+    while i + 1 < tiles.len() {
+        if tiles[i] == tiles[i + 1] {
+            tiles[i] *= 2;
+            tiles[i + 1] = 0;
+            score += tiles[i];
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    let mut result: Vec<u32> = tiles.into_iter().filter(|val| *val != 0).collect();
+    result.resize(row.len(), 0);
+    (result, score)
+}
+
+#[test]
+fn test_row_move_against_reference() {
+    let tile_values = [0, 2, 4, 8, 16];
+    for len in 1..=5u32 {
+        for n in 0..tile_values.len().pow(len) {
+            // enumerate every row of the given length
+            let mut code = n;
+            let mut row = Vec::new();
+            for _ in 0..len {
+                row.push(tile_values[code % tile_values.len()]);
+                code /= tile_values.len();
+            }
+
+            let (expected, expected_score) = reference_move_row_left(&row);
+            let (result, info) = move_values_left(&row);
+            assert_eq!(result, expected, "move left {:?}", row);
+            assert_eq!(info.score, expected_score);
+            assert_eq!(info.moved, result != row);
+
+            let mut row_rev = row.clone();
+            row_rev.reverse();
+            let (mut expected, expected_score) = reference_move_row_left(&row_rev);
+            expected.reverse();
+            let (result, info) = move_values_right(&row);
+            assert_eq!(result, expected, "move right {:?}", row);
+            assert_eq!(info.score, expected_score);
+            assert_eq!(info.moved, result != row);
+        }
+    }
+}
@@ -1,7 +1,11 @@
 
 extern crate wasm_terminal_2048;
-use wasm_terminal_2048::game::{Game};
+use wasm_terminal_2048::args::SIZE_RANGE;
+use wasm_terminal_2048::game::{Game, GameStatus};
+use wasm_terminal_2048::solver::{Solver, DEFAULT_DEPTH};
+use wasm_terminal_2048::spawn::{Difficulty, SpawnPolicy};
 use wasm_terminal_2048::tile::{Direction};
+use std::convert::TryFrom;
 
 use wasm_bindgen::prelude::*;
 
@@ -11,10 +15,23 @@ pub const CONTROL_TEXT: &str = "    ============= 2048 ============= \n\r
     UP-DOWN-RIGHT-LEFT: direction \n\r
     k-j-h-l: direction \n\r
     n: new game \n\r
+    c: keep going after winning \n\r
+    u-r: undo-redo \n\r
     ================================ \n\r
     Current steps:  "; 
+pub const SCORE_TEXT: &str = "    Score: ";
+pub const BEST_SCORE_TEXT: &str = "    Best: ";
+pub const SEED_TEXT: &str = "    Seed: ";
+pub const DIFFICULTY_TEXT: &str = "    Difficulty: ";
+pub const UNDOS_LEFT_TEXT: &str = "    Undos left: ";
+pub const WON_TEXT: &str = "    *** You win! press c to keep going, n for a new game *** \n\r";
+pub const LOST_TEXT: &str = "    *** Game over! press n for a new game *** \n\r";
 }
 
+/// The number of nodes searched by the solver per movement, since the time budget is not
+/// supported in the browser
+const SOLVER_NODE_BUDGET: u64 = 200_000;
+
 #[wasm_bindgen]
 extern "C" {
     fn alert(s: &str);
@@ -22,18 +39,24 @@ extern "C" {
 
 #[wasm_bindgen]
 pub struct GameController {
-    steps: u32,
-    game: Game, 
+    game: Game<Box<dyn SpawnPolicy + Send>>, 
+    difficulty: Difficulty,
+}
+
+impl Default for GameController {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[wasm_bindgen]
 impl GameController {
 
     pub fn new() -> Self {
-        let game = Game::new();
+        let difficulty = Difficulty::default();
         GameController {
-            steps: 0,
-            game: game,
+            game: Game::with_policy(difficulty.policy()),
+            difficulty,
         }
     }
 
@@ -43,34 +66,132 @@ impl GameController {
     }
 
     pub fn get_steps(&self) -> u32 {
-        self.steps
+        self.game.get_steps()
+    }
+
+    pub fn get_score(&self) -> u32 {
+        self.game.get_score()
+    }
+
+    pub fn get_best_score(&self) -> u32 {
+        self.game.get_best_score()
+    }
+
+    pub fn is_won(&self) -> bool {
+        self.game.get_status() == GameStatus::Won
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.game.get_status() == GameStatus::Lost
+    }
+
+    pub fn keep_going(&mut self) -> bool {
+        self.game.keep_going()
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.game.get_seed()
+    }
+
+    /// Change the difficulty level by the name, e.g. `hard`, which is applied from the next
+    /// generated tile. Returns false if the name is unknown.
+    pub fn set_difficulty(&mut self, name: &str) -> bool {
+        match Difficulty::from_name(name) {
+            Some(difficulty) => {
+                self.difficulty = difficulty;
+                self.game.set_policy(difficulty.policy());
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn get_difficulty(&self) -> String {
+        self.difficulty.name().to_string()
+    }
+
+    /// Take back the last action, returns false if nothing is undone
+    pub fn undo(&mut self) -> bool {
+        self.game.undo()
+    }
+
+    /// Apply the last undone action again, returns false if nothing is redone
+    pub fn redo(&mut self) -> bool {
+        self.game.redo()
+    }
+
+    /// Change the number of rows and columns of the board, and each side is from 2 to 8. The
+    /// current game is cleared at once, and `start_new_game` should be called to play with the
+    /// new size. Returns false if the size is invalid, and the game is kept.
+    pub fn set_size(&mut self, rows: usize, cols: usize) -> bool {
+        if SIZE_RANGE.contains(&rows) && SIZE_RANGE.contains(&cols) {
+            self.game.set_size(rows, cols);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Limit the number of undos per game, `undefined` means unlimited
+    pub fn set_undo_limit(&mut self, limit: Option<u32>) {
+        self.game.set_undo_limit(limit);
     }
 
     pub fn start_new_game(&mut self) {
        self.game.start_game();
-       self.steps = 0;
     }
 
-    pub fn next(&mut self) -> bool {
-        self.steps += 1;
-        self.game.next()
+    /// Start a new game that can be reproduced by the given seed
+    pub fn start_new_game_with_seed(&mut self, seed: u64) {
+       self.game.start_game_with_seed(seed);
+    }
+
+    /// Returns true if any tile is moved, which means a new tile is generated
+    pub fn action(&mut self, direction: i32) -> bool {
+        match Direction::try_from(direction) {
+            Ok(dir) => self.game.action(dir).moved,
+            Err(_) => false,
+        }
+    }
+
+    /// Find the best movement of the current board by the solver. Returns the direction code,
+    /// or -1 if no movement is possible.
+    pub fn best_move(&self) -> i32 {
+        let mut solver = Solver::new(DEFAULT_DEPTH);
+        solver.set_node_budget(Some(SOLVER_NODE_BUDGET));
+        solver.best_move(self.game.get_board()).map_or(-1, |dir| dir as i32)
+    }
+
+    fn status_text(&self) -> &'static str {
+        match self.game.get_status() {
+            GameStatus::Won => gui::WON_TEXT,
+            GameStatus::Lost => gui::LOST_TEXT,
+            GameStatus::Playing | GameStatus::WonContinuing => "",
+        }
     }
 
-    pub fn action(&mut self, direction: i32) {
-        match direction  {
-            0  => self.game.action(Direction::Left),
-            1  => self.game.action(Direction::Right),
-            2  => self.game.action(Direction::Up),
-            3  => self.game.action(Direction::Down),
-            _ => {}
+    fn undos_left_text(&self) -> String {
+        match self.game.get_undos_left() {
+            Some(left) => format!("{}{}\n\r", gui::UNDOS_LEFT_TEXT, left),
+            None => String::new(),
         }
     }
 
     pub fn render(&self) -> String {
-        format!("{}{}{}\n\r", 
+        format!("{}{}{}{}\n\r{}{}\n\r{}{}\n\r{}{}\n\r{}{}\n\r{}", 
             self.game.get_board(),
+            self.status_text(),
             gui::CONTROL_TEXT,
-            self.game.get_steps())
+            self.game.get_steps(),
+            gui::SCORE_TEXT,
+            self.game.get_score(),
+            gui::BEST_SCORE_TEXT,
+            self.game.get_best_score(),
+            gui::SEED_TEXT,
+            self.game.get_seed(),
+            gui::DIFFICULTY_TEXT,
+            self.difficulty.name(),
+            self.undos_left_text())
     }
 }
 
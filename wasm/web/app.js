@@ -12,41 +12,47 @@ export default class App {
 
   input_key_handler() {
     this.terminal.onKey(e => {
-            let valid_key = true;
-            let flag_start_new_game = false;
+            let moved = false;
+            let flag_render_only = false;
 
             switch(e.domEvent.keyCode) {
               case 37: // Left
               case 72: // h 
-                this.game_controller.action(Direction.Left);
+                moved = this.game_controller.action(Direction.Left);
                 break;
               case 39: // Right
               case 76: // l
-                this.game_controller.action(Direction.Right);
+                moved = this.game_controller.action(Direction.Right);
                 break;
               case 38: // Up
               case 75: // k
-                this.game_controller.action(Direction.Up);
+                moved = this.game_controller.action(Direction.Up);
                 break;
               case 40: // Down
               case 74: // j 
-                this.game_controller.action(Direction.Down);
+                moved = this.game_controller.action(Direction.Down);
                 break;
               case 78: // n
                 this.game_controller.start_new_game();
-                flag_start_new_game = true;
+                flag_render_only = true;
+                break;
+              case 67: // c
+                this.game_controller.keep_going();
+                flag_render_only = true;
+                break;
+              case 85: // u
+                moved = this.game_controller.undo();
+                break;
+              case 82: // r
+                moved = this.game_controller.redo();
                 break;
               default:
-                valid_key = false;
+                break;
             }
 
-            
-            if(valid_key && !flag_start_new_game) {
-              let r = this.game_controller.next();
-              if(r)
-                this.render();
-            } else {
-                this.render();
+            // a new tile is generated only if any tile is moved
+            if(moved || flag_render_only) {
+              this.render();
             }
 
       });
@@ -60,7 +66,30 @@ export default class App {
   }
 
   run() {
-    this.game_controller.start_new_game();
+    const params = new URLSearchParams(window.location.search);
+    // the difficulty level given in the url, e.g. `?difficulty=hard`
+    const difficulty = params.get("difficulty");
+    if(difficulty !== null) {
+      this.game_controller.set_difficulty(difficulty);
+    }
+    // the board size given in the url, e.g. `?size=5x5`
+    const size = params.get("size");
+    if(size !== null && /^[0-9]+x[0-9]+$/.test(size)) {
+      const [rows, cols] = size.split("x").map(side => parseInt(side));
+      this.game_controller.set_size(rows, cols);
+    }
+    // the number of undos per game given in the url, e.g. `?undo_limit=3`
+    const undo_limit = params.get("undo_limit");
+    if(undo_limit !== null && /^[0-9]+$/.test(undo_limit)) {
+      this.game_controller.set_undo_limit(parseInt(undo_limit));
+    }
+    // the game can be reproduced by the seed given in the url, e.g. `?seed=42`
+    const seed = params.get("seed");
+    if(seed !== null && /^[0-9]+$/.test(seed)) {
+      this.game_controller.start_new_game_with_seed(BigInt(seed));
+    } else {
+      this.game_controller.start_new_game();
+    }
     this.render();
   }
 